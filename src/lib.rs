@@ -1,32 +1,662 @@
 use imgui as imgui_rs;
 use imgui_rs::{Context, DrawData, TextureId};
-use skia_safe::{AlphaType, Paint};
-use std::collections::HashMap;
+use skia_safe::{AlphaType, Paint, QuickReject};
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::Arc;
 
 pub struct Renderer {
     // this holds the skia formatted font atlas
     images: HashMap<usize, skia_safe::Paint>,
-    img_idx: usize // this is incremented each time an image is registered and is the id returned to the caller
+    texture_infos: HashMap<usize, TextureInfo>, // kept alongside images, see texture_info
+    ninepatches: HashMap<usize, NinePatch>, // textures registered with register_ninepatch
+    effects: HashMap<usize, EffectTexture>, // textures registered with register_effect
+    texture_transforms: HashMap<usize, (skia_safe::Shader, skia_safe::Matrix)>, // see set_texture_matrix, with the shader from before it
+    img_idx: usize, // this is incremented each time an image is registered and is the id returned to the caller
+    clip_op: skia_safe::ClipOp, // applied to every command's clip rect, intersect unless the caller wants to punch holes
+    clip_quality: ClipQuality, // see set_clip_quality
+    clip_mode: ClipMode, // see set_clip_mode
+    font_image: Arc<skia_safe::Image>, // the A8 atlas behind the font paint, may be shared with other renderers
+    font_texture_id: TextureId, // the atlas' texture id, imgui's FontAtlas::tex_id has to match it
+    white_pixel_uv: Option<skia_safe::Point>, // the atlas' white texel, None when we didn't bake the atlas ourselves
+    solid_paint: skia_safe::Paint, // see set_solid_paint
+    multisampled: bool, // set by the caller when the target is MSAA, anti-aliased clips double blend against its coverage
+    pixel_art: bool, // hard clip edges for integer scaled pixel art, see load_image_pixel_art
+    strict_uv: bool, // debug aid, see set_strict_uv
+    skip_transparent: bool, // see set_skip_transparent
+    max_draw_calls: Option<usize>, // see set_max_draw_calls
+    debug_texture_colors: bool, // debug aid, see set_debug_texture_colors
+    layer_paint: Option<skia_safe::Paint>, // when set the whole ui is drawn into a layer composited with this paint
+    layer_bounds: Option<skia_safe::Rect>,
+    retained: Option<RetainedFrame>, // the cached output of render_retained
+    frame_stats: RefCell<FrameStats>, // filled in while rendering so render_imgui can stay &self
+    font_blend_mode: skia_safe::BlendMode, // how vertex colors combine with the font atlas' A8 coverage
+    image_blend_mode: skia_safe::BlendMode, // how vertex colors combine with every other texture
+    round_to_device_pixels: bool, // see set_round_to_device_pixels
+    device_pixel_ratio: f32, // see set_device_pixel_ratio
+    texture_resolver: Option<TextureResolver>, // consulted when a texture id isn't registered
+    before_draw_list: Option<DrawListHook>, // see set_draw_list_hooks
+    after_draw_list: Option<DrawListHook>,
+    frame_open: bool, // between begin_frame and end_frame
+    mask: Option<skia_safe::Image>, // see set_mask
+    opaque_first: bool, // see set_opaque_first
+    group_additive: bool, // see set_group_additive
+    outline: Option<Outline>, // see set_outline
+    outer_clip: Option<skia_safe::Rect>, // see set_outer_clip
+    font_config_hash: Option<u64>, // the context's font setup when we last built the atlas from it, see needs_font_rebuild
+    pending_updates: Vec<(TextureId, skia_safe::Paint, bool)>, // update_image calls held back until end_frame, true for update_image's plain replacements
+    uploads_enabled: bool, // see set_upload_enabled
+    deferred_uploads: Vec<(TextureId, skia_safe::Paint)>, // registrations and updates held back until uploads resume
+    last_error: RefCell<Option<RendererError>>, // the latest problem render_imgui skipped over instead of panicking
+    frame_number: Cell<u64>, // counts rendered frames for evict_unused
+    texture_last_used: RefCell<HashMap<usize, u64>>, // the frame_number each texture was last drawn (or registered) in
+    validated_lists: RefCell<HashMap<usize, u64>>, // draw list index to the fingerprint of the last list there whose indices checked out
+    defer_software_cursor: bool, // leave imgui's software cursor for render_software_cursor
+    clip_exempt_lists: HashSet<usize>, // draw list indices rendered without imgui's clip rects
+    clip_exempt_textures: HashSet<TextureId>, // same, for every command sampling these textures
+    backdrop_filters: HashMap<TextureId, skia_safe::ImageFilter>, // see set_backdrop_filter
+    global_tint: skia_safe::Color, // multiplied into every vertex color, white leaves the ui untouched
+}
+
+// imgui's RenderMouseCursor adds two shadow quads, a border quad and a fill quad
+const SOFTWARE_CURSOR_INDICES: usize = 4 * 6;
+
+#[derive(Clone, Copy, PartialEq)]
+enum CursorPass {
+    Include,
+    Exclude,
+    Only,
+}
+
+// the scratch buffers imgui's vertices are translated into, reusable across frames
+#[derive(Default)]
+pub struct FrameArena {
+    idx: Vec<u16>,
+    pos: Vec<skia_safe::Point>,
+    uv: Vec<skia_safe::Point>,
+    color: Vec<skia_safe::Color>,
+    commands: Vec<PreparedCommand>, // the current list's commands, see prepare_commands
+    save_counts: Vec<(usize, usize)>, // a CanvasPass's, kept between frames along with everything else
+}
+
+impl FrameArena {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // sized for the largest single draw list expected, buffers are reused list to list
+    pub fn with_capacity(vertices: usize, indices: usize) -> Self {
+        FrameArena {
+            idx: Vec::with_capacity(indices),
+            pos: Vec::with_capacity(vertices),
+            uv: Vec::with_capacity(vertices),
+            color: Vec::with_capacity(vertices),
+            commands: Vec::new(),
+            save_counts: Vec::new(),
+        }
+    }
+}
+
+// one Elements command as Renderer::draw_commands reports it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DrawCommandInfo {
+    pub list_index: usize,
+    pub texture_id: TextureId,
+    pub clip_rect: [f32; 4], // min x, min y, max x, max y
+    pub idx_offset: usize,
+    pub count: usize,
+    pub vtx_offset: usize,
+}
+
+// a frame translated by Renderer::prepare, drawn with Renderer::render_batch
+pub struct GeometryBatch {
+    lists: Vec<PreparedList>,
+}
+
+struct PreparedList {
+    list_index: usize,
+    geometry: FrameArena,
+    commands: Vec<PreparedCommand>,
+}
+
+// an Elements command with its clip in device space and the cursor pass already applied to its index range
+#[derive(Clone, Copy)]
+struct PreparedCommand {
+    texture_id: TextureId,
+    clip_rect: skia_safe::Rect,
+    vtx_offset: usize,
+    idx_offset: usize,
+    count: usize,
+}
+
+// save counts and clip state for the canvases one render call draws into
+struct CanvasPass {
+    // per canvas, the incoming save count to put it back to and the count after the layers, restoring to
+    // which drops the current clip
+    save_counts: Vec<(usize, usize)>,
+    // nested windows emit long runs of commands with the same clip, so we only save and clip when it changes
+    // None is the canvas's own clip, which clip exempt commands draw with. the clip only depends on the
+    // command, so every canvas is always in the same clip state
+    current_clip: Option<skia_safe::Rect>,
+}
+
+// what the last rendered frame submitted, broken down by texture to find what's driving the geometry
+#[derive(Clone, Debug, Default)]
+pub struct FrameStats {
+    pub draw_calls: usize,
+    pub vertices: usize, // every index drawn counts as a vertex, shared vertices included
+    pub triangles: usize,
+    pub textures: HashMap<TextureId, TextureStats>,
+    pub clip_changes: usize, // each is a restore and save on every canvas, see ClipMode
+    pub unique_clips: usize, // distinct clip rects drawn with, far fewer than clip_changes means windows interleave
+    pub uniform_color_draws: usize, // draws whose vertices all shared a color, sent to skia as the paint color instead
+    pub solid_draws: usize, // font commands that only sampled the white pixel, drawn with the solid paint
+    pub revalidated_lists: usize, // draw lists whose indices were checked, the rest matched last frame's fingerprint
+    #[cfg(feature = "timing")]
+    pub translate_time: std::time::Duration, // building skia's vertex buffers from imgui's, zero for render_batch
+    #[cfg(feature = "timing")]
+    pub draw_time: std::time::Duration, // clipping and issuing draw_vertices
+    clips_seen: HashSet<[u32; 4]>, // the bits of each clip rect counted in unique_clips
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextureStats {
+    pub draw_calls: usize,
+    pub vertices: usize,
+    pub triangles: usize,
+    // total area of the texture's triangles in pixels before clipping, much larger than the area the
+    // texture visibly covers means it's being overdrawn
+    pub covered_area: f32,
+}
+
+impl FrameStats {
+    // zeroes everything for a new frame, the maps keep their capacity so a steady ui doesn't reallocate them
+    fn reset(&mut self) {
+        let mut textures = std::mem::take(&mut self.textures);
+        let mut clips_seen = std::mem::take(&mut self.clips_seen);
+        textures.clear();
+        clips_seen.clear();
+
+        *self = FrameStats { textures, clips_seen, ..FrameStats::default() };
+    }
+
+    fn record_clip(&mut self, clip: skia_safe::Rect) {
+        if self.clips_seen.insert([clip.left.to_bits(), clip.top.to_bits(), clip.right.to_bits(), clip.bottom.to_bits()]) {
+            self.unique_clips += 1;
+        }
+    }
+
+    fn record(&mut self, texture_id: TextureId, pos: &[skia_safe::Point], indices: &[u16]) {
+        let mut covered_area = 0.0;
+        for triangle in indices.chunks_exact(3) {
+            let a = pos[triangle[0] as usize];
+            let b = pos[triangle[1] as usize];
+            let c = pos[triangle[2] as usize];
+            covered_area += ((b.x - a.x) * (c.y - a.y) - (c.x - a.x) * (b.y - a.y)).abs() * 0.5;
+        }
+
+        self.draw_calls += 1;
+        self.vertices += indices.len();
+        self.triangles += indices.len() / 3;
+
+        let texture_stats = self.textures.entry(texture_id).or_default();
+        texture_stats.draw_calls += 1;
+        texture_stats.vertices += indices.len();
+        texture_stats.triangles += indices.len() / 3;
+        texture_stats.covered_area += covered_area;
+    }
+}
+
+struct RetainedFrame {
+    surface: skia_safe::Surface,
+    matrix: skia_safe::Matrix, // the canvas matrix the surface was drawn under
+    list_bounds: Vec<Option<skia_safe::Rect>>, // the device space bounds each draw list had when the surface was last drawn
+}
+
+#[derive(Debug)]
+pub enum RendererError {
+    DecodeFailed, // skia didn't recognise the encoded image or couldn't decode it
+    EmptyFontAtlas, // the font atlas baked to a zero sized texture, usually because no font could be loaded
+    SurfaceCreationFailed, // skia couldn't allocate an offscreen surface of the requested size
+    EncodeFailed,
+    Io(std::io::Error),
+    UnknownTexture(TextureId), // draw data referenced a texture id that was never registered or was released
+    UnknownUniform(String), // the runtime effect has no uniform by that name
+    InvalidUniforms(usize, usize), // uniform data of this many bytes was given where the effect expects that many
+    ShaderCreationFailed, // the runtime effect wouldn't make a shader from its uniforms
+    UploadDeferred(TextureId), // the texture is held back until uploads are enabled again, see set_upload_enabled
+    TextureIdInUse(TextureId), // register_image_with_id was given an id that's already registered
+    DrawCallBudgetExceeded(usize), // the frame had more commands than set_max_draw_calls allows, the rest weren't drawn
+    InvalidIndices(usize), // a command in this draw list indexed past its buffers, it was skipped
+    InvalidRect(skia_safe::IRect), // the rect is empty, too small, or reaches outside the image
+    InvalidImageSize(i32, i32), // the width and height are empty, or more pixels than the buffer holds
+    InvalidDevicePixelRatio(f32), // the ratio is zero, negative, infinite or nan
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RendererError::DecodeFailed => write!(f, "failed to decode image"),
+            RendererError::EmptyFontAtlas => write!(f, "font atlas is empty"),
+            RendererError::SurfaceCreationFailed => write!(f, "failed to create surface"),
+            RendererError::EncodeFailed => write!(f, "failed to encode image"),
+            RendererError::Io(err) => write!(f, "io error: {}", err),
+            RendererError::UnknownTexture(texid) => write!(f, "unknown texture id {}", texid.id()),
+            RendererError::UnknownUniform(name) => write!(f, "unknown uniform {}", name),
+            RendererError::InvalidUniforms(given, expected) => write!(f, "got {} bytes of uniforms, expected {}", given, expected),
+            RendererError::ShaderCreationFailed => write!(f, "failed to create shader from runtime effect"),
+            RendererError::UploadDeferred(texid) => write!(f, "texture id {} is waiting for uploads to resume", texid.id()),
+            RendererError::TextureIdInUse(texid) => write!(f, "texture id {} is already registered", texid.id()),
+            RendererError::DrawCallBudgetExceeded(max) => write!(f, "frame exceeded the budget of {} draw calls", max),
+            RendererError::InvalidIndices(list_index) => write!(f, "draw list {} has indices out of range", list_index),
+            RendererError::InvalidRect(rect) => write!(f, "invalid rect ({}, {}, {}, {})", rect.left, rect.top, rect.right, rect.bottom),
+            RendererError::InvalidImageSize(width, height) => write!(f, "invalid image size {}x{}", width, height),
+            RendererError::InvalidDevicePixelRatio(ratio) => write!(f, "invalid device pixel ratio {}", ratio),
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for RendererError {
+    fn from(err: std::io::Error) -> Self {
+        RendererError::Io(err)
+    }
+}
+
+// a borrowed view of imgui's A8 font atlas pixels
+// the pixels belong to the atlas and are only valid until it is rebuilt or cleared, so to_image copies
+// them into skia owned memory rather than handing skia a pointer into the atlas
+pub struct FontTextureView<'a> {
+    width: u32,
+    height: u32,
+    data: &'a [u8],
+}
+
+impl<'a> FontTextureView<'a> {
+    pub fn new(texture: imgui::FontAtlasTexture<'a>) -> Self {
+        FontTextureView {
+            width: texture.width,
+            height: texture.height,
+            data: texture.data,
+        }
+    }
+
+    // a view over alpha8 atlas bytes captured elsewhere, e.g. saved alongside draw data for replay
+    pub fn from_a8(data: &'a [u8], width: u32, height: u32) -> Self {
+        assert!(data.len() >= width as usize * height as usize, "atlas buffer is too small for its dimensions");
+
+        FontTextureView { width, height, data }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn to_image(&self) -> Option<skia_safe::Image> {
+        let dimensions = skia_safe::ISize::new(self.width as i32, self.height as i32);
+        let imgfont_a8 = skia_safe::ImageInfo::new_a8(dimensions);
+
+        // imgui packs the atlas rows tightly, one byte per texel, so the stride we hand skia is the width
+        // rather than whatever skia would pick. if skia ever wants more than that per row we repack
+        let packed_row_bytes = self.width as usize;
+        let packed_size = packed_row_bytes * self.height as usize;
+        if self.data.len() < packed_size {
+            return None;
+        }
+
+        let row_bytes = imgfont_a8.min_row_bytes();
+        if row_bytes <= packed_row_bytes {
+            let pixels = skia_safe::Data::new_copy(&self.data[..packed_size]);
+            return skia_safe::Image::from_raster_data(&imgfont_a8, pixels, packed_row_bytes);
+        }
+
+        let mut padded = vec![0u8; row_bytes * self.height as usize];
+        for (dst_row, src_row) in padded.chunks_exact_mut(row_bytes).zip(self.data.chunks_exact(packed_row_bytes)) {
+            dst_row[..packed_row_bytes].copy_from_slice(src_row);
+        }
+
+        skia_safe::Image::from_raster_data(&imgfont_a8, skia_safe::Data::new_copy(&padded), row_bytes)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    Bgra8,
+    A8,
+    Other(skia_safe::ColorType),
+}
+
+impl TextureFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::Rgba8 | TextureFormat::Bgra8 => 4,
+            TextureFormat::A8 => 1,
+            TextureFormat::Other(color_type) => color_type.bytes_per_pixel(),
+        }
+    }
+
+    fn from_color_type(color_type: skia_safe::ColorType) -> Self {
+        match color_type {
+            skia_safe::ColorType::RGBA8888 => TextureFormat::Rgba8,
+            skia_safe::ColorType::BGRA8888 => TextureFormat::Bgra8,
+            skia_safe::ColorType::Alpha8 => TextureFormat::A8,
+            other => TextureFormat::Other(other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextureInfo {
+    pub width: i32,
+    pub height: i32,
+    pub format: TextureFormat,
+    pub opaque: bool, // the image has no alpha channel, or skia knows every pixel is opaque
+}
+
+// how command clip rects are applied when the canvas is rotated or skewed, axis aligned clips are the same either way
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipQuality {
+    Fast,        // clip_rect, anti-aliased unless the target is multisampled or pixel art
+    HighQuality, // always anti-aliased path clips under non axis aligned transforms, for rotated canvases like node editors
+}
+
+// when the canvas is saved and clipped for command clip rects, both draw the same pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClipMode {
+    Shared,     // a restore, save and clip only when the clip changes, commands sharing the previous one draw inside it
+    PerCommand, // a restore, save and clip around every command, what a canvas guard per command would cost
+}
+
+// the layout of raw pixel buffers passed to load_image, one entry per skia color type we know how to feed it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PixelFormat {
+    Rgba8888,
+    Bgra8888,
+    A8,      // coverage only, drawn in the vertex color the same way the font atlas is
+    Gray8,
+    Rgb888x, // four bytes per pixel with the last one ignored, always opaque
+}
+
+impl PixelFormat {
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 | PixelFormat::Rgb888x => 4,
+            PixelFormat::A8 | PixelFormat::Gray8 => 1,
+        }
+    }
+
+    fn color_type(&self) -> skia_safe::ColorType {
+        match self {
+            PixelFormat::Rgba8888 => skia_safe::ColorType::RGBA8888,
+            PixelFormat::Bgra8888 => skia_safe::ColorType::BGRA8888,
+            PixelFormat::A8 => skia_safe::ColorType::Alpha8,
+            PixelFormat::Gray8 => skia_safe::ColorType::Gray8,
+            PixelFormat::Rgb888x => skia_safe::ColorType::RGB888x,
+        }
+    }
+
+    fn alpha_type(&self) -> AlphaType {
+        match self {
+            // straight alpha, skia premultiplies on sampling so translucent texels composite onto a premultiplied
+            // destination with the right alpha. an unknown alpha type leaves that conversion up to skia's defaults
+            PixelFormat::Rgba8888 | PixelFormat::Bgra8888 => AlphaType::Unpremul,
+            PixelFormat::A8 => AlphaType::Premul,
+            PixelFormat::Gray8 | PixelFormat::Rgb888x => AlphaType::Opaque,
+        }
+    }
+}
+
+// border widths of a nine-patch texture in pixels, measured in from each edge
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Insets {
+    pub left: i32,
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+// a stroke drawn around solid ui shapes, see set_outline
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    pub color: skia_safe::Color,
+    pub width: f32,
+}
+
+#[derive(Clone)]
+struct EffectTexture {
+    effect: skia_safe::RuntimeEffect,
+    uniforms: Vec<u8>, // the effect's uniform block as last set, laid out as skia reports it
+}
+
+#[derive(Clone)]
+struct NinePatch {
+    image: skia_safe::Image,
+    center: skia_safe::IRect, // the stretched middle region, everything outside it is corners and edges
+}
+
+// the registered textures of a renderer, moved into its replacement with import_state. paints hold references
+// to their skia images, so no pixels are copied and the state lives only as long as the process
+#[derive(Clone, Default)]
+pub struct RendererState {
+    images: HashMap<usize, skia_safe::Paint>,
+    ninepatches: HashMap<usize, NinePatch>,
+    effects: HashMap<usize, EffectTexture>,
+    texture_transforms: HashMap<usize, (skia_safe::Shader, skia_safe::Matrix)>,
+    next_id: usize,
+}
+
+pub type TextureResolver = Box<dyn Fn(TextureId) -> Option<skia_safe::Paint> + Send>;
+
+// called with each canvas and the draw list's index, see set_draw_list_hooks
+pub type DrawListHook = Box<dyn Fn(&mut skia_safe::Canvas, usize) + Send>;
+
+// where the first row of an image buffer sits, camera and video buffers are often stored bottom-up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Origin {
+    TopLeft,
+    BottomLeft,
 }
 
 impl Renderer {
+    // tightly packed pixels in any of the formats PixelFormat covers, load_image_rgba8 and friends are shorthands
+    pub fn load_image(pixels: &[u8], width: i32, height: i32, format: PixelFormat) -> skia_safe::Paint {
+        Self::load_image_with(pixels, width, height, width as usize * format.bytes_per_pixel(), Origin::TopLeft, format, format.alpha_type())
+    }
+
     pub fn load_image_rgba8(img: &[u8], width: i32, height: i32) -> skia_safe::Paint {
+        Self::load_image(img, width, height, PixelFormat::Rgba8888)
+    }
+
+    // for pixels whose color channels are already multiplied by alpha, such as gpu readbacks. load_image_rgba8
+    // treats its pixels as straight alpha, so skia would multiply the alpha in a second time at semi-transparent
+    // edges, darkening them
+    pub fn load_image_rgba8_premul(img: &[u8], width: i32, height: i32) -> skia_safe::Paint {
+        Self::load_image_with(img, width, height, width as usize * 4, Origin::TopLeft, PixelFormat::Rgba8888, AlphaType::Premul)
+    }
+
+    // stride is the number of bytes between the start of two rows, which may be larger than width * 4
+    pub fn load_image_strided(img: &[u8], width: i32, height: i32, stride: usize, origin: Origin) -> skia_safe::Paint {
+        Self::load_image_with(img, width, height, stride, origin, PixelFormat::Rgba8888, PixelFormat::Rgba8888.alpha_type())
+    }
+
+    fn load_image_with(img: &[u8], width: i32, height: i32, stride: usize, origin: Origin, format: PixelFormat, alpha_type: AlphaType) -> skia_safe::Paint {
+        let image = Self::raster_image(img, width, height, stride, format, alpha_type);
+
+        let mut local_matrix = skia_safe::Matrix::scale((1.0 / width as f32, 1.0 / height as f32));
+        if origin == Origin::BottomLeft {
+            // flip v so that uv (0, 0) still lands on the top row as imgui expects
+            local_matrix.post_scale((1.0, -1.0), None);
+            local_matrix.post_translate((0.0, 1.0));
+        }
+
+        Self::build_image_paint(&image, &local_matrix)
+    }
+
+    // decodes png, jpeg or anything else skia has a codec for
+    pub fn load_image_encoded(encoded: &[u8]) -> Result<skia_safe::Paint, RendererError> {
+        let image = skia_safe::Image::from_encoded(skia_safe::Data::new_copy(encoded)).ok_or(RendererError::DecodeFailed)?;
+        let local_matrix = skia_safe::Matrix::scale((1.0 / image.width() as f32, 1.0 / image.height() as f32));
+
+        Ok(Self::build_image_paint(&image, &local_matrix))
+    }
+
+    // strict nearest sampling and decal tiling (transparent outside the image) so textures magnified by
+    // integer factors stay blocky without edge texels wrapping round, pair it with set_pixel_art
+    pub fn load_image_pixel_art(img: &[u8], width: i32, height: i32) -> skia_safe::Paint {
+        let image = Self::raster_image_rgba8(img, width, height);
+
+        let local_matrix = skia_safe::Matrix::scale((1.0 / width as f32, 1.0 / height as f32));
+        let sampling_options = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Nearest, skia_safe::MipmapMode::None);
+
+        Self::build_image_paint_with(&image, &local_matrix, sampling_options, skia_safe::TileMode::Decal)
+    }
+
+    // transform is applied in normalized uv space after the 1 / width, 1 / height scale, so mirroring horizontally
+    // is Matrix::scale((-1.0, 1.0)) followed by a translate of (1.0, 0.0)
+    pub fn register_image_transformed(&mut self, img: &[u8], width: i32, height: i32, transform: skia_safe::Matrix) -> TextureId {
+        let image = Self::raster_image_rgba8(img, width, height);
+
+        let mut local_matrix = skia_safe::Matrix::scale((1.0 / width as f32, 1.0 / height as f32));
+        local_matrix.post_concat(&transform);
+
+        self.register_image(Self::build_image_paint(&image, &local_matrix))
+    }
+
+    // a snapshot is the surface's contents at the time of the call, drawing to the surface afterwards doesn't
+    // show up in the texture. for live content like a minimap pass load_surface_snapshot to update_image each frame
+    pub fn register_surface_snapshot(&mut self, surface: &mut skia_safe::Surface) -> TextureId {
+        self.register_image(Self::load_surface_snapshot(surface))
+    }
+
+    pub fn load_surface_snapshot(surface: &mut skia_safe::Surface) -> skia_safe::Paint {
+        let image = surface.image_snapshot();
+        let local_matrix = skia_safe::Matrix::scale((1.0 / image.width() as f32, 1.0 / image.height() as f32));
+
+        Self::build_image_paint(&image, &local_matrix)
+    }
+
+    // registers an rgba8 image as usual, so imgui can still draw it stretched, and also remembers its insets
+    // (the fixed size border on each side) so draw_ninepatch can scale just the edges and center
+    pub fn register_ninepatch(&mut self, img: &[u8], width: i32, height: i32, insets: Insets) -> TextureId {
+        let image = Self::raster_image_rgba8(img, width, height);
+        let local_matrix = skia_safe::Matrix::scale((1.0 / width as f32, 1.0 / height as f32));
+        let texid = self.register_image(Self::build_image_paint(&image, &local_matrix));
+
+        let center = skia_safe::IRect::new(insets.left, insets.top, width - insets.right, height - insets.bottom);
+        self.ninepatches.insert(texid.id(), NinePatch { image, center });
+
+        texid
+    }
+
+    // corners are drawn at their original size, edges stretch along one axis and the center along both
+    pub fn draw_ninepatch(&self, canvas: &mut skia_safe::Canvas, texid: TextureId, dst_rect: skia_safe::Rect) -> Result<(), RendererError> {
+        let ninepatch = self.ninepatches.get(&texid.id()).ok_or(RendererError::UnknownTexture(texid))?;
+        canvas.draw_image_nine(&ninepatch.image, ninepatch.center, dst_rect, skia_safe::FilterMode::Nearest, None);
+
+        Ok(())
+    }
+
+    // a texture whose pixels come from an sksl shader (animated noise, a palette swap), commands drawing it
+    // shade with the effect in place of an image. the shader's coords are imgui's uvs, so 0 to 1 over the quad
+    // uniforms is the effect's whole uniform block, set_effect_uniform updates single values afterwards
+    // the effect can't read vertex colors: they're only combined with its output by the image blend mode, and
+    // handing them to sksl needs drawVertices with an SkBlender, which skia-safe 0.50 doesn't bind. per vertex
+    // inputs like a dissolve amount have to ride in the uvs instead, the shader sees them interpolated as coords
+    pub fn register_effect(&mut self, effect: skia_safe::RuntimeEffect, uniforms: Vec<u8>) -> Result<TextureId, RendererError> {
+        if uniforms.len() != effect.uniform_size() {
+            return Err(RendererError::InvalidUniforms(uniforms.len(), effect.uniform_size()));
+        }
+
+        let paint = Self::effect_paint(&effect, &uniforms)?;
+        let texid = self.register_image(paint);
+        self.effects.insert(texid.id(), EffectTexture { effect, uniforms });
+
+        Ok(texid)
+    }
+
+    // writes values into the named float uniform (a float, float2, half4, ...) of an effect texture, e.g. a time
+    // uniform once per frame. the paint is rebuilt straight away and swapped in like update_image does. on an
+    // error the texture keeps the uniforms it had
+    pub fn set_effect_uniform(&mut self, texid: TextureId, name: &str, values: &[f32]) -> Result<(), RendererError> {
+        let effect_texture = self.effects.get_mut(&texid.id()).ok_or(RendererError::UnknownTexture(texid))?;
+
+        let c_name = std::ffi::CString::new(name).map_err(|_| RendererError::UnknownUniform(name.to_string()))?;
+        let uniform = effect_texture.effect.find_uniform(c_name).ok_or_else(|| RendererError::UnknownUniform(name.to_string()))?;
+        if uniform.size_in_bytes() != values.len() * 4 {
+            return Err(RendererError::InvalidUniforms(values.len() * 4, uniform.size_in_bytes()));
+        }
+
+        let offset = uniform.offset();
+        let mut uniforms = effect_texture.uniforms.clone();
+        for (i, value) in values.iter().enumerate() {
+            uniforms[offset + i * 4 .. offset + i * 4 + 4].copy_from_slice(&value.to_ne_bytes());
+        }
+
+        let paint = Self::effect_paint(&effect_texture.effect, &uniforms)?;
+        effect_texture.uniforms = uniforms;
+        self.replace_paint(texid, paint);
+
+        Ok(())
+    }
+
+    fn effect_paint(effect: &skia_safe::RuntimeEffect, uniforms: &[u8]) -> Result<skia_safe::Paint, RendererError> {
+        let shader = effect.make_shader(skia_safe::Data::new_copy(uniforms), &[], None).ok_or(RendererError::ShaderCreationFailed)?;
+
         let mut paint = Paint::default();
+        paint.set_shader(shader);
+        paint.set_color(skia_safe::Color::WHITE);
+
+        Ok(paint)
+    }
+
+    fn raster_image_rgba8(img: &[u8], width: i32, height: i32) -> skia_safe::Image {
+        Self::raster_image(img, width, height, width as usize * 4, PixelFormat::Rgba8888, PixelFormat::Rgba8888.alpha_type())
+    }
+
+    fn raster_image(img: &[u8], width: i32, height: i32, stride: usize, format: PixelFormat, alpha_type: AlphaType) -> skia_safe::Image {
         let dimensions = skia_safe::ISize::new(width, height);
-        let img_info_rgba8 = skia_safe::ImageInfo::new_n32(dimensions, AlphaType::Unknown, None);
+        // imgui ui is authored in sRGB, tagging the pixels as such lets skia convert them to the destination's color
+        // space the same way it already does vertex colors. on an untagged destination nothing changes
+        let img_info = skia_safe::ImageInfo::new(dimensions, format.color_type(), alpha_type, skia_safe::ColorSpace::new_srgb());
 
-        let pixels = unsafe {
-            skia_safe::Data::new_bytes(img)
-        };
+        assert!(stride >= img_info.min_row_bytes(), "stride is smaller than a row of pixels");
+        assert!(img.len() >= img_info.compute_byte_size(stride), "image buffer is too small for its stride");
 
-        let pixmap = skia_safe::Pixmap::new(&img_info_rgba8, img, img_info_rgba8.min_row_bytes());
-        let image = skia_safe::Image::from_raster_data(&img_info_rgba8, pixels, pixmap.row_bytes());
+        // copy the pixels so the image doesn't outlive a buffer the caller (or we) may drop
+        let pixels = skia_safe::Data::new_copy(&img[..img_info.compute_byte_size(stride)]);
+        skia_safe::Image::from_raster_data(&img_info, pixels, stride).unwrap()
+    }
 
-        let local_matrix = skia_safe::Matrix::scale((1.0 / width as f32, 1.0 / height as f32));
+    fn build_image_paint(image: &skia_safe::Image, local_matrix: &skia_safe::Matrix) -> skia_safe::Paint {
         let sampling_options = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Nearest, skia_safe::MipmapMode::None);
-        let tile_mode = skia_safe::TileMode::Repeat;
+        Self::build_image_paint_with(image, local_matrix, sampling_options, skia_safe::TileMode::Repeat)
+    }
+
+    fn build_image_paint_with(image: &skia_safe::Image, local_matrix: &skia_safe::Matrix, sampling_options: skia_safe::SamplingOptions, tile_mode: skia_safe::TileMode) -> skia_safe::Paint {
+        let mut paint = Paint::default();
 
-        let image_shader = image.unwrap().to_shader((tile_mode, tile_mode), sampling_options, &local_matrix);
+        let image_shader = image.to_shader((tile_mode, tile_mode), sampling_options, local_matrix);
 
         paint.set_shader(image_shader);
         paint.set_color(skia_safe::Color::WHITE);
@@ -34,124 +664,2054 @@ impl Renderer {
         return paint;
     }
 
+    // texels whose rgb matches key become fully transparent, done once here rather than per frame in a shader
+    pub fn load_image_rgba8_colorkey(img: &[u8], width: i32, height: i32, key: skia_safe::Color) -> skia_safe::Paint {
+        let mut keyed = img.to_vec();
+        for texel in keyed.chunks_exact_mut(4) {
+            if texel[0] == key.r() && texel[1] == key.g() && texel[2] == key.b() {
+                texel.copy_from_slice(&[0, 0, 0, 0]);
+            }
+        }
+
+        Self::load_image_rgba8(&keyed, width, height)
+    }
+
+    // while uploads are paused the id is handed out straight away but the texture only arrives once they resume
     pub fn register_image(&mut self, paint: skia_safe::Paint) -> TextureId {
-        self.images.insert(self.img_idx, paint);
+        // ids taken by register_image_with_id are stepped over rather than replaced
+        while self.is_id_in_use(self.img_idx) {
+            self.img_idx += 1;
+        }
+
+        let texid = TextureId::new(self.img_idx);
+        self.insert_image(texid, paint);
         self.img_idx += 1;
-        return TextureId::new(self.img_idx - 1);
+        return texid;
     }
 
-    pub fn update_image(&mut self, texid: &TextureId, paint: skia_safe::Paint) {
-        self.images.insert(texid.id(), paint);
+    // registers under an id of the caller's choosing, e.g. one derived from an asset key, so draw data saved in
+    // an earlier run still resolves. register_image keeps counting up from where it was and skips ids taken here,
+    // so a large or hashed id doesn't push the small indices it hands out anywhere near a pointer's range
+    pub fn register_image_with_id(&mut self, id: usize, paint: skia_safe::Paint) -> Result<TextureId, RendererError> {
+        let texid = TextureId::new(id);
+        if self.is_id_in_use(id) {
+            return Err(RendererError::TextureIdInUse(texid));
+        }
+
+        self.insert_image(texid, paint);
+
+        Ok(texid)
     }
 
-    pub fn release_image(&mut self, texid: TextureId) {
-        self.images.remove(&texid.id());
+    fn is_id_in_use(&self, id: usize) -> bool {
+        self.images.contains_key(&id) || self.deferred_uploads.iter().any(|(deferred, _)| deferred.id() == id)
     }
 
-    fn build_paint(atlas: &mut imgui::FontAtlasRefMut, font_paint: &mut skia_safe::Paint)
-    {
-        let imfont_texture = atlas.build_alpha8_texture();
-        let dimensions = skia_safe::ISize::new(imfont_texture.width as i32, imfont_texture.height as i32);
-        let imgfont_a8 = skia_safe::ImageInfo::new_a8(dimensions);
-        
-        let pixels = unsafe {
-            skia_safe::Data::new_bytes(imfont_texture.data)
-        };
+    // loads and registers each rgba8 image in turn, one result per image in the same order. an image whose
+    // buffer doesn't fit its size fails on its own instead of panicking in load_image_rgba8, the rest still register
+    pub fn register_images<'a>(&mut self, images: impl Iterator<Item = (&'a [u8], i32, i32)>) -> Vec<Result<TextureId, RendererError>> {
+        images
+            .map(|(img, width, height)| {
+                Self::check_image_size(img, width, height)?;
+                Ok(self.register_image(Self::load_image_rgba8(img, width, height)))
+            })
+            .collect()
+    }
 
-        let pixmap = skia_safe::Pixmap::new(&imgfont_a8, imfont_texture.data, imgfont_a8.min_row_bytes());
-        let font_image = skia_safe::Image::from_raster_data(&imgfont_a8, pixels, pixmap.row_bytes());
+    fn check_image_size(img: &[u8], width: i32, height: i32) -> Result<(), RendererError> {
+        let byte_size = (width as usize).checked_mul(height as usize).and_then(|texels| texels.checked_mul(4));
+        match byte_size {
+            Some(byte_size) if width > 0 && height > 0 && img.len() >= byte_size => Ok(()),
+            _ => Err(RendererError::InvalidImageSize(width, height)),
+        }
+    }
 
-        let local_matrix = skia_safe::Matrix::scale((1.0 / imfont_texture.width as f32, 1.0 / imfont_texture.height as f32));
-        let sampling_options = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Nearest, skia_safe::MipmapMode::None);
-        let tile_mode = skia_safe::TileMode::Repeat;
+    fn insert_image(&mut self, texid: TextureId, paint: skia_safe::Paint) {
+        self.texture_last_used.get_mut().insert(texid.id(), self.frame_number.get());
+        if self.should_defer_upload(texid) {
+            self.deferred_uploads.push((texid, paint));
+        } else {
+            self.record_texture_info(texid.id(), &paint);
+            self.images.insert(texid.id(), paint);
+        }
+    }
 
-        let font_shader = font_image.unwrap().to_shader((tile_mode, tile_mode), sampling_options, &local_matrix);
+    // lets memory constrained apps hold back new textures: while disabled, register_image and update_image keep
+    // their paints aside instead of making them drawable, so skia never uploads them. commands drawing a held
+    // back texture are skipped with RendererError::UploadDeferred. enabling again makes them all drawable in the
+    // order they came in. the font atlas is exempt, the ui can't draw without it
+    pub fn set_upload_enabled(&mut self, uploads_enabled: bool) {
+        self.uploads_enabled = uploads_enabled;
+        if uploads_enabled {
+            for (texid, paint) in std::mem::take(&mut self.deferred_uploads) {
+                self.replace_paint(texid, paint);
+            }
+        }
+    }
 
-        font_paint.set_shader(font_shader);
-        font_paint.set_color(skia_safe::Color::WHITE);
+    fn should_defer_upload(&self, texid: TextureId) -> bool {
+        !self.uploads_enabled && !self.is_font_texture(texid)
     }
 
-    pub fn new(im_context: &mut Context) -> Self
-    {
-        let mut ret = Renderer {
-            images: HashMap::new(),
-            img_idx: 0,
-        };
+    // every registered paint, the font atlas included, for bulk edits like tinting all images at once
+    // mutating paints between render calls is fine, keeping that from racing a frame in flight is up to the caller
+    pub fn images_mut(&mut self) -> impl Iterator<Item = (TextureId, &mut skia_safe::Paint)> + '_ {
+        self.images.iter_mut().map(|(id, paint)| (TextureId::new(*id), paint))
+    }
 
-        let mut font_paint = skia_safe::Paint::default();
-        Self::build_paint(&mut im_context.fonts(), &mut font_paint);
-        ret.register_image(font_paint);
+    // reserves a texture id showing a magenta and black checkerboard, swap in the real pixels with update_image
+    // once they've streamed in so draw data can reference the id straight away
+    pub fn register_placeholder(&mut self) -> TextureId {
+        const CHECKER_SIZE: i32 = 8;
 
-        ret
+        let mut pixels = Vec::with_capacity((CHECKER_SIZE * CHECKER_SIZE * 4) as usize);
+        for y in 0..CHECKER_SIZE {
+            for x in 0..CHECKER_SIZE {
+                if (x + y) % 2 == 0 {
+                    pixels.extend_from_slice(&[255, 0, 255, 255]);
+                } else {
+                    pixels.extend_from_slice(&[0, 0, 0, 255]);
+                }
+            }
+        }
+
+        self.register_image(Self::load_image_rgba8(&pixels, CHECKER_SIZE, CHECKER_SIZE))
     }
 
-    pub fn render_imgui(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, )
-    {
-        for draw_list in data.draw_lists() {
-            let mut idx: Vec<u16> = Vec::new();
-            let mut pos: Vec<skia_safe::Point> = Vec::new();
-            let mut uv: Vec<skia_safe::Point> = Vec::new();
-            let mut color: Vec<skia_safe::Color> = Vec::new();
-
-            // we've got to translate the vertex buffer from imgui into Skia friendly types
-            // thankfully skia_safe gives us a constructor for Color so we don't have to swizzle the colors as Skia expects BGR order
-            for vertex in draw_list.vtx_buffer() {
-                pos.push(skia_safe::Point {
-                    x: vertex.pos[0],
-                    y: vertex.pos[1]
-                });
+    // between begin_frame and end_frame the new paint is queued rather than swapped in, see begin_frame
+    pub fn update_image(&mut self, texid: &TextureId, paint: skia_safe::Paint) {
+        // the replacement is a plain paint, draw_ninepatch and set_effect_uniform would keep using the old texture
+        // otherwise. in a frame they go when the paint is swapped at end_frame, so the frame sees the old texture whole
+        if self.frame_open && !self.should_defer_upload(*texid) {
+            self.pending_updates.push((*texid, paint, true));
+        } else {
+            self.remove_texture_extras(*texid);
+            self.replace_paint(*texid, paint);
+        }
+    }
 
-                uv.push(skia_safe::Point {
-                    x: vertex.uv[0],
-                    y: vertex.uv[1]
-                });
+    fn replace_paint(&mut self, texid: TextureId, paint: skia_safe::Paint) {
+        if self.should_defer_upload(texid) {
+            self.deferred_uploads.push((texid, paint));
+        } else if self.frame_open {
+            self.pending_updates.push((texid, paint, false));
+        } else {
+            self.apply_update(texid, paint);
+        }
+    }
 
-                color.push(skia_safe::Color::from_argb(
-                    vertex.col[3],
-                    vertex.col[0],
-                    vertex.col[1],
-                    vertex.col[2],
-                ));
-            }
-            
-            // we build our index buffer
-            for index in draw_list.idx_buffer() {
-                idx.push(*index);
-            }
-
-            // so now we've got to loop through imgui's cmd buffer and draw everything with canvas.draw_vertices
-            for cmd in draw_list.commands() {
-                let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
-                match cmd {
-                    imgui::DrawCmd::RawCallback {
-                        ..
-                    } => {
-                        todo!("Raw callbacks unimplemented!")
-                    }
-                    imgui::DrawCmd::ResetRenderState => {
-                        todo!("Reset render state unimplemented!")
-                    }
-                    imgui::DrawCmd::Elements {
-                        count,
-                        cmd_params,
-                    } => {
-                        //TODO: Handle images that aren't our font atlas
-                        let id_index = cmd_params.texture_id;
-                        let paint = &self.images[&id_index.id()];
-
-                        let clip_rect = cmd_params.clip_rect;
-                        let skclip_rect = skia_safe::Rect::new(clip_rect[0], clip_rect[1], clip_rect[2], clip_rect[3]);
-
-                        let vertex_mode = skia_safe::vertices::VertexMode::Triangles;
-                        let idx_offset = cmd_params.idx_offset;
-                        let idx_slice = Some(&idx[idx_offset .. idx_offset + count]);
-
-                        arc.clip_rect(skclip_rect, skia_safe::ClipOp::default(), true);
-                        let vertices = skia_safe::Vertices::new_copy(vertex_mode, &pos, &uv, &color, idx_slice);
-                        arc.draw_vertices(&vertices, skia_safe::BlendMode::Modulate, paint);
-                    }
-                }
+    // what a texture carries besides its paint, a plain replacement or a release drops all of it
+    fn remove_texture_extras(&mut self, texid: TextureId) {
+        self.ninepatches.remove(&texid.id());
+        self.effects.remove(&texid.id());
+        self.texture_transforms.remove(&texid.id());
+    }
+
+    fn apply_update(&mut self, texid: TextureId, paint: skia_safe::Paint) {
+        self.record_texture_info(texid.id(), &paint);
+        self.images.insert(texid.id(), paint);
+    }
+
+    // marks the start of a frame whose draws may be spread over several render calls (a window per call,
+    // a frame recorded across threads). until end_frame, update_image only queues the new paint, so every
+    // draw in the frame sees the same version of each texture no matter when the update came in
+    pub fn begin_frame(&mut self) {
+        self.frame_open = true;
+    }
+
+    // applies the updates queued since begin_frame in the order they were made, the last one for an id wins
+    pub fn end_frame(&mut self) {
+        self.frame_open = false;
+        *self.frame_number.get_mut() += 1;
+        for (texid, paint, plain) in std::mem::take(&mut self.pending_updates) {
+            if plain {
+                self.remove_texture_extras(texid);
             }
+            self.apply_update(texid, paint);
         }
     }
-}
+
+    pub fn release_image(&mut self, texid: TextureId) {
+        self.images.remove(&texid.id());
+        self.texture_infos.remove(&texid.id());
+        self.remove_texture_extras(texid);
+        self.texture_last_used.get_mut().remove(&texid.id());
+        self.pending_updates.retain(|(pending, _, _)| *pending != texid);
+        self.deferred_uploads.retain(|(deferred, _)| *deferred != texid);
+    }
+
+    // for hot reloading: export before the old renderer is dropped and import into the new one straight after
+    // it's created, so draw data keeps resolving to the same ids. the font atlas isn't part of the state, the new
+    // renderer builds its own. textures held back by set_upload_enabled are included
+    pub fn export_state(&self) -> RendererState {
+        let mut images: HashMap<usize, skia_safe::Paint> = self.images
+            .iter()
+            .filter(|(id, _)| **id != self.font_texture_id.id())
+            .map(|(id, paint)| (*id, paint.clone()))
+            .collect();
+        for (texid, paint) in &self.deferred_uploads {
+            images.insert(texid.id(), paint.clone());
+        }
+
+        RendererState {
+            images,
+            ninepatches: self.ninepatches.clone(),
+            effects: self.effects.clone(),
+            texture_transforms: self.texture_transforms.clone(),
+            next_id: self.img_idx,
+        }
+    }
+
+    // imported ids replace whatever this renderer registered under the same ids, and ids registered from now on
+    // carry on after the exported renderer's
+    pub fn import_state(&mut self, state: RendererState) {
+        for (id, paint) in state.images {
+            self.texture_last_used.get_mut().insert(id, self.frame_number.get());
+            self.replace_paint(TextureId::new(id), paint);
+        }
+        self.ninepatches.extend(state.ninepatches);
+        self.effects.extend(state.effects);
+        self.texture_transforms.extend(state.texture_transforms);
+        self.img_idx = self.img_idx.max(state.next_id);
+    }
+
+    // releases every texture that hasn't been drawn in the last older_than_frames frames and hands back their
+    // ids, so the caller can drop whatever it keeps alongside them. a frame is one render call, or everything
+    // between begin_frame and end_frame. newly registered textures count as used, the font atlas is never evicted
+    pub fn evict_unused(&mut self, older_than_frames: u32) -> Vec<TextureId> {
+        let frame_number = self.frame_number.get();
+        let font_texture_id = self.font_texture_id;
+        let last_used = self.texture_last_used.get_mut();
+
+        // resolver handles get stamped when drawn too, but they aren't ours to evict
+        let images = &self.images;
+        last_used.retain(|id, _| images.contains_key(id));
+
+        let evicted: Vec<TextureId> = self.images
+            .keys()
+            .map(|id| TextureId::new(*id))
+            .filter(|texid| *texid != font_texture_id)
+            .filter(|texid| frame_number - last_used.get(&texid.id()).copied().unwrap_or(0) > older_than_frames as u64)
+            .collect();
+
+        for texid in &evicted {
+            self.release_image(*texid);
+        }
+
+        evicted
+    }
+
+    // lets textures be produced on demand by a key packed into the TextureId, for virtualized texture systems
+    // the resolver is only asked about ids that aren't registered, and is asked again every time they're drawn,
+    // so it should cache whatever it materializes. if it returns None the command is skipped as unknown
+    // the key can be an opaque handle too, like a gpu texture pointer another backend packed in with
+    // TextureId::from(ptr). ids handed out by register_image are small indices counting up from 0 (the font
+    // atlas), so a real address never lands on one and both kinds can be mixed in the same draw data. ids picked
+    // with register_image_with_id are the caller's to keep clear of any handle the resolver might see
+    pub fn set_texture_resolver(&mut self, resolver: Option<TextureResolver>) {
+        self.texture_resolver = resolver;
+    }
+
+    fn texture_paint(&self, texid: TextureId) -> Option<Cow<'_, skia_safe::Paint>> {
+        match self.images.get(&texid.id()) {
+            Some(paint) => Some(Cow::Borrowed(paint)),
+            None => self.texture_resolver.as_ref().and_then(|resolver| resolver(texid)).map(Cow::Owned),
+        }
+    }
+
+    // checks every command's texture is registered without drawing anything, so a frame referencing a released
+    // or never registered texture can be caught up front instead of showing up as missing draws in last_error
+    // the unknown ids come back once each, in the order the draw data first uses them
+    pub fn validate_draw_data(&self, data: &DrawData) -> Result<(), Vec<TextureId>> {
+        let mut unknown = Vec::new();
+
+        for command in Self::draw_commands(data) {
+            let texture_id = command.texture_id;
+            if self.texture_paint(texture_id).is_none() && !unknown.contains(&texture_id) {
+                unknown.push(texture_id);
+            }
+        }
+
+        if unknown.is_empty() { Ok(()) } else { Err(unknown) }
+    }
+
+    // resamples a registered image texture with different sampling, keeping its tiling and uv mapping. every
+    // loader samples nearest, which suits pixel aligned ui. for textures drawn minified or at an angle (a rotated
+    // node editor background) trilinear filtering is the closest we get, skia-safe 0.50 has no anisotropic
+    // sampling, and mipmaps get built here when the sampling asks for them
+    pub fn set_texture_sampling(&mut self, texid: TextureId, sampling: skia_safe::SamplingOptions) -> Result<(), RendererError> {
+        let paint = self.images.get(&texid.id()).ok_or(RendererError::UnknownTexture(texid))?;
+        // resample the shader from before set_texture_matrix, the transform is put back on top afterwards
+        let shader = match self.texture_transforms.get(&texid.id()) {
+            Some((base_shader, _)) => Some(base_shader.clone()),
+            None => paint.shader(),
+        };
+        let (image, local_matrix, tile_modes) = shader
+            .and_then(|shader| shader.image())
+            .ok_or(RendererError::UnknownTexture(texid))?;
+
+        let image = if sampling.mipmap != skia_safe::MipmapMode::None && !image.has_mipmaps() {
+            image.with_default_mipmaps().unwrap_or(image)
+        } else {
+            image
+        };
+
+        let mut resampled = paint.clone();
+        let resampled_shader = image.to_shader(tile_modes, sampling, &local_matrix);
+        match (resampled_shader, self.texture_transforms.get_mut(&texid.id())) {
+            (Some(resampled_shader), Some((base_shader, transform))) => {
+                resampled.set_shader(resampled_shader.with_local_matrix(transform));
+                *base_shader = resampled_shader;
+            }
+            (resampled_shader, _) => {
+                resampled.set_shader(resampled_shader);
+            }
+        }
+        self.replace_paint(texid, resampled);
+
+        Ok(())
+    }
+
+    // moves a texture within its quads without touching the image, e.g. scrolling water or a marquee by
+    // updating a translation every frame. transform is in normalized uv space like register_image_transformed's,
+    // so translating by (0.25, 0.0) shifts the image a quarter of its width to the right. each call replaces the
+    // previous transform rather than adding to it, and update_image drops it along with the old paint
+    pub fn set_texture_matrix(&mut self, texid: TextureId, transform: skia_safe::Matrix) -> Result<(), RendererError> {
+        let paint = self.images.get(&texid.id()).ok_or(RendererError::UnknownTexture(texid))?;
+        let base_shader = match self.texture_transforms.get(&texid.id()) {
+            Some((base_shader, _)) => base_shader.clone(),
+            None => paint.shader().ok_or(RendererError::UnknownTexture(texid))?,
+        };
+
+        let mut moved = paint.clone();
+        moved.set_shader(base_shader.with_local_matrix(&transform));
+        self.texture_transforms.insert(texid.id(), (base_shader, transform));
+        self.replace_paint(texid, moved);
+
+        Ok(())
+    }
+
+    // a second texture id over the same image as existing, sampled and tiled its own way, e.g. a pattern that
+    // repeats in one widget and clamps in another. the pixels aren't copied, both ids keep the image alive
+    // the uv mapping is existing's, and updating either id afterwards leaves the other untouched
+    pub fn register_alias(&mut self, existing: TextureId, sampling: skia_safe::SamplingOptions, tile: skia_safe::TileMode) -> Result<TextureId, RendererError> {
+        let paint = self.images.get(&existing.id()).ok_or(RendererError::UnknownTexture(existing))?;
+        let (image, local_matrix, _) = paint.shader()
+            .and_then(|shader| shader.image())
+            .ok_or(RendererError::UnknownTexture(existing))?;
+
+        let alias = Self::build_image_paint_with(&image, &local_matrix, sampling, tile);
+
+        Ok(self.register_image(alias))
+    }
+
+    // a texture id showing just rect of existing's image, for sprites packed into one atlas. uv 0 to 1 spans the
+    // rect and the pixels are shared. with linear sampling texels next to the rect bleed in along its edges,
+    // half_texel_inset shrinks the sampled range to the centers of the rect's outer texels so they can't
+    // (sprites lose half a texel on each side). edges clamp, as far as the atlas goes. rect has to lie within
+    // the image, and be at least 2 texels across for the inset, or it's RendererError::InvalidRect
+    pub fn register_sub_image(&mut self, existing: TextureId, rect: skia_safe::IRect, sampling: skia_safe::SamplingOptions, half_texel_inset: bool) -> Result<TextureId, RendererError> {
+        let paint = self.images.get(&existing.id()).ok_or(RendererError::UnknownTexture(existing))?;
+        let (image, _, _) = paint.shader()
+            .and_then(|shader| shader.image())
+            .ok_or(RendererError::UnknownTexture(existing))?;
+
+        let min_size = if half_texel_inset { 2 } else { 1 };
+        let within_image = rect.left >= 0 && rect.top >= 0 && rect.right <= image.width() && rect.bottom <= image.height();
+        if !within_image || rect.width() < min_size || rect.height() < min_size {
+            return Err(RendererError::InvalidRect(rect));
+        }
+
+        let mut sampled = skia_safe::Rect::from_irect(rect);
+        if half_texel_inset {
+            sampled.inset((0.5, 0.5));
+        }
+
+        let mut local_matrix = skia_safe::Matrix::translate((-sampled.left, -sampled.top));
+        local_matrix.post_scale((1.0 / sampled.width(), 1.0 / sampled.height()), None);
+
+        let sub_image = Self::build_image_paint_with(&image, &local_matrix, sampling, skia_safe::TileMode::Clamp);
+
+        Ok(self.register_image(sub_image))
+    }
+
+    // dimensions and pixel format of the image behind a texture, as of when it was registered or last updated
+    // None for unknown ids and for paints that aren't backed by an image shader
+    pub fn texture_info(&self, texid: TextureId) -> Option<TextureInfo> {
+        self.texture_infos.get(&texid.id()).copied()
+    }
+
+    // rough memory footprint of every registered texture, font atlas included, from width * height * bytes per pixel
+    // mipmaps, gpu padding and shared images registered under several ids aren't accounted for
+    pub fn texture_memory_bytes(&self) -> usize {
+        self.texture_infos
+            .values()
+            .map(|info| info.width as usize * info.height as usize * info.format.bytes_per_pixel())
+            .sum()
+    }
+
+    fn record_texture_info(&mut self, id: usize, paint: &skia_safe::Paint) {
+        match paint.shader().and_then(|shader| shader.image()) {
+            Some((image, _, _)) => {
+                self.texture_infos.insert(id, TextureInfo {
+                    width: image.width(),
+                    height: image.height(),
+                    format: TextureFormat::from_color_type(image.color_type()),
+                    opaque: image.is_opaque(),
+                });
+            }
+            None => {
+                self.texture_infos.remove(&id);
+            }
+        }
+    }
+
+    fn build_font_image(atlas: &mut imgui::FontAtlasRefMut) -> Result<skia_safe::Image, RendererError>
+    {
+        // the white pixel uv is only known once the atlas is baked, imgui caches the bake so asking for the
+        // texture twice is cheap and keeps the texture's borrow of the atlas from overlapping the read
+        atlas.build_alpha8_texture();
+        let white_pixel_uv = Self::atlas_white_pixel_uv(atlas);
+
+        let imfont_texture = FontTextureView::new(atlas.build_alpha8_texture());
+
+        // imgui hands back a zero sized texture when no font could be baked
+        if imfont_texture.width() == 0 || imfont_texture.height() == 0 {
+            return Err(RendererError::EmptyFontAtlas);
+        }
+
+        // every solid shape imgui draws samples this one texel, so it has to come out as full coverage
+        // or fills render slightly off white, if it doesn't we patch our copy of the atlas
+        let white_x = ((white_pixel_uv[0] * imfont_texture.width() as f32) as usize).min(imfont_texture.width() as usize - 1);
+        let white_y = ((white_pixel_uv[1] * imfont_texture.height() as f32) as usize).min(imfont_texture.height() as usize - 1);
+        let white_idx = white_y * imfont_texture.width() as usize + white_x;
+
+        if imfont_texture.data()[white_idx] != 0xFF {
+            let mut patched = imfont_texture.data().to_vec();
+            patched[white_idx] = 0xFF;
+
+            let patched_texture = FontTextureView {
+                width: imfont_texture.width(),
+                height: imfont_texture.height(),
+                data: &patched,
+            };
+            return patched_texture.to_image().ok_or(RendererError::EmptyFontAtlas);
+        }
+
+        imfont_texture.to_image().ok_or(RendererError::EmptyFontAtlas)
+    }
+
+    fn atlas_white_pixel_uv(atlas: &imgui::FontAtlas) -> [f32; 2]
+    {
+        // imgui-rs keeps this field private, but FontAtlas is a repr(C) mirror of ImFontAtlas so we can read it from there
+        let raw_atlas = unsafe { &*(atlas as *const imgui::FontAtlas as *const imgui::sys::ImFontAtlas) };
+        [raw_atlas.TexUvWhitePixel.x, raw_atlas.TexUvWhitePixel.y]
+    }
+
+    // hashes everything imgui's bake depends on so it can be checked before baking. the font data goes in by
+    // where it lives rather than its bytes, which is cheap enough to check every frame
+    fn font_config_hash(atlas: &imgui::FontAtlas) -> u64
+    {
+        let raw_atlas = unsafe { &*(atlas as *const imgui::FontAtlas as *const imgui::sys::ImFontAtlas) };
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+
+        raw_atlas.Flags.hash(&mut hasher);
+        raw_atlas.TexDesiredWidth.hash(&mut hasher);
+        raw_atlas.TexGlyphPadding.hash(&mut hasher);
+
+        let configs = match raw_atlas.ConfigData.Size {
+            0 => &[][..],
+            size => unsafe { std::slice::from_raw_parts(raw_atlas.ConfigData.Data, size as usize) },
+        };
+
+        for config in configs {
+            config.FontData.hash(&mut hasher);
+            config.FontDataSize.hash(&mut hasher);
+            config.FontNo.hash(&mut hasher);
+            config.SizePixels.to_bits().hash(&mut hasher);
+            config.OversampleH.hash(&mut hasher);
+            config.OversampleV.hash(&mut hasher);
+            config.PixelSnapH.hash(&mut hasher);
+            for value in &[config.GlyphExtraSpacing.x, config.GlyphExtraSpacing.y, config.GlyphOffset.x, config.GlyphOffset.y] {
+                value.to_bits().hash(&mut hasher);
+            }
+            config.GlyphMinAdvanceX.to_bits().hash(&mut hasher);
+            config.GlyphMaxAdvanceX.to_bits().hash(&mut hasher);
+            config.MergeMode.hash(&mut hasher);
+            config.RasterizerFlags.hash(&mut hasher);
+            config.RasterizerMultiply.to_bits().hash(&mut hasher);
+            config.EllipsisChar.hash(&mut hasher);
+
+            // glyph ranges are pairs of code points terminated by a zero
+            if !config.GlyphRanges.is_null() {
+                let mut range = config.GlyphRanges;
+                unsafe {
+                    while *range != 0 {
+                        (*range).hash(&mut hasher);
+                        range = range.add(1);
+                    }
+                }
+            }
+        }
+
+        hasher.finish()
+    }
+
+    // the uv imgui uses for untextured geometry, only meaningful once the atlas has been built
+    pub fn font_white_pixel_uv(im_context: &mut Context) -> [f32; 2]
+    {
+        Self::atlas_white_pixel_uv(&im_context.fonts())
+    }
+
+    // maps imgui's 0 to 1 uvs onto the atlas' pixels, right for any atlas imgui baked itself
+    fn font_local_matrix(font_image: &skia_safe::Image) -> skia_safe::Matrix
+    {
+        skia_safe::Matrix::scale((1.0 / font_image.width() as f32, 1.0 / font_image.height() as f32))
+    }
+
+    fn build_paint(font_image: &skia_safe::Image, local_matrix: &skia_safe::Matrix, font_paint: &mut skia_safe::Paint)
+    {
+        let sampling_options = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Nearest, skia_safe::MipmapMode::None);
+        let tile_mode = skia_safe::TileMode::Repeat;
+
+        let font_shader = font_image.to_shader((tile_mode, tile_mode), sampling_options, local_matrix);
+
+        font_paint.set_shader(font_shader);
+        font_paint.set_color(skia_safe::Color::WHITE);
+    }
+
+    // if the font atlas can't be built the renderer starts with fallback_font_atlas instead, so the app can still
+    // draw an error ui, and the reason is left in last_error. use try_new to handle the failure up front
+    pub fn new(im_context: &mut Context) -> Self
+    {
+        Self::try_new(im_context).unwrap_or_else(|err| {
+            let renderer = Self::with_shared_font_atlas(Self::fallback_font_atlas());
+            im_context.fonts().tex_id = renderer.font_texture_id;
+            *renderer.last_error.borrow_mut() = Some(err);
+            renderer
+        })
+    }
+
+    // a single fully covered texel, the atlas repeats so every uv imgui hands out samples it. shapes draw
+    // as normal and text comes out as solid boxes, which is enough for a "fonts failed to load" screen
+    pub fn fallback_font_atlas() -> Arc<skia_safe::Image>
+    {
+        let image = FontTextureView::from_a8(&[0xFF], 1, 1).to_image().expect("failed to build the fallback font atlas");
+        Arc::new(image)
+    }
+
+    pub fn try_new(im_context: &mut Context) -> Result<Self, RendererError>
+    {
+        // we honour each command's vtx_offset, which lets imgui go past 64k vertices per list with 16 bit indices
+        im_context.io_mut().backend_flags.insert(imgui::BackendFlags::RENDERER_HAS_VTX_OFFSET);
+
+        let mut renderer = Self::with_shared_font_atlas(Self::shared_font_atlas(im_context)?);
+        renderer.font_config_hash = Some(Self::font_config_hash(&im_context.fonts()));
+        let [white_u, white_v] = Self::font_white_pixel_uv(im_context);
+        renderer.white_pixel_uv = Some(skia_safe::Point::new(white_u, white_v));
+        im_context.fonts().tex_id = renderer.font_texture_id;
+
+        Ok(renderer)
+    }
+
+    // builds the font atlas image once so several renderers (one per window, say) can share it
+    // instead of each uploading their own copy of the same A8 texture
+    pub fn shared_font_atlas(im_context: &mut Context) -> Result<Arc<skia_safe::Image>, RendererError>
+    {
+        Ok(Arc::new(Self::build_font_image(&mut im_context.fonts())?))
+    }
+
+    // builds the font atlas from captured A8 bytes instead of a live Context, render_imgui never touches the
+    // Context so a renderer made this way can replay captured draw data once its other textures are registered
+    // the captured atlas has to be the one the draw data was produced against or the glyph uvs won't line up
+    pub fn captured_font_atlas(data: &[u8], width: u32, height: u32) -> Result<Arc<skia_safe::Image>, RendererError>
+    {
+        if width == 0 || height == 0 {
+            return Err(RendererError::EmptyFontAtlas);
+        }
+        // checked here so a short capture is an error instead of from_a8's assert
+        match (width as usize).checked_mul(height as usize) {
+            Some(texels) if data.len() >= texels => {}
+            _ => return Err(RendererError::InvalidImageSize(width as i32, height as i32)),
+        }
+
+        let image = FontTextureView::from_a8(data, width, height).to_image().ok_or(RendererError::EmptyFontAtlas)?;
+        Ok(Arc::new(image))
+    }
+
+    // also the way to get a renderer without an imgui Context at all, e.g. in tests. a synthetic atlas filled
+    // with 0xFF makes every font textured command draw solid, which is what fills and lines expect
+    pub fn from_captured_font_atlas(data: &[u8], width: u32, height: u32) -> Result<Self, RendererError>
+    {
+        Ok(Self::with_shared_font_atlas(Self::captured_font_atlas(data, width, height)?))
+    }
+
+    pub fn with_shared_font_atlas(font_image: Arc<skia_safe::Image>) -> Self
+    {
+        let local_matrix = Self::font_local_matrix(&font_image);
+        Self::with_font_atlas_matrix(font_image, local_matrix)
+    }
+
+    // for an externally prepared atlas whose layout doesn't match imgui's uvs one to one, local_matrix takes
+    // the atlas' pixel coordinates to the uvs the draw data uses (identity for an image already in uv space)
+    // configure_fonts and reset go back to the usual mapping, since they rebuild the atlas from imgui
+    pub fn with_font_atlas_matrix(font_image: Arc<skia_safe::Image>, local_matrix: skia_safe::Matrix) -> Self
+    {
+        let mut font_paint = skia_safe::Paint::default();
+        Self::build_paint(&font_image, &local_matrix, &mut font_paint);
+
+        let mut ret = Renderer {
+            images: HashMap::new(),
+            texture_infos: HashMap::new(),
+            ninepatches: HashMap::new(),
+            effects: HashMap::new(),
+            texture_transforms: HashMap::new(),
+            img_idx: 0,
+            clip_op: skia_safe::ClipOp::default(),
+            clip_quality: ClipQuality::Fast,
+            clip_mode: ClipMode::Shared,
+            font_image,
+            font_texture_id: TextureId::new(0), // assigned below once the atlas is registered
+            white_pixel_uv: None,
+            solid_paint: Self::default_solid_paint(),
+            multisampled: false,
+            pixel_art: false,
+            strict_uv: false,
+            skip_transparent: false,
+            max_draw_calls: None,
+            debug_texture_colors: false,
+            layer_paint: None,
+            layer_bounds: None,
+            retained: None,
+            frame_stats: RefCell::new(FrameStats::default()),
+            font_blend_mode: skia_safe::BlendMode::Modulate,
+            image_blend_mode: skia_safe::BlendMode::Modulate,
+            round_to_device_pixels: false,
+            device_pixel_ratio: 1.0,
+            last_error: RefCell::new(None),
+            frame_number: Cell::new(0),
+            texture_last_used: RefCell::new(HashMap::new()),
+            validated_lists: RefCell::new(HashMap::new()),
+            texture_resolver: None,
+            before_draw_list: None,
+            after_draw_list: None,
+            frame_open: false,
+            mask: None,
+            opaque_first: false,
+            group_additive: false,
+            outline: None,
+            outer_clip: None,
+            font_config_hash: None,
+            pending_updates: Vec::new(),
+            uploads_enabled: true,
+            deferred_uploads: Vec::new(),
+            defer_software_cursor: false,
+            clip_exempt_lists: HashSet::new(),
+            clip_exempt_textures: HashSet::new(),
+            backdrop_filters: HashMap::new(),
+            global_tint: skia_safe::Color::WHITE,
+        };
+
+        ret.font_texture_id = ret.register_image(font_paint);
+
+        ret
+    }
+
+    // hint that the target surface is multisampled so clip edges are left to the MSAA resolve
+    pub fn set_multisampled(&mut self, multisampled: bool) {
+        self.multisampled = multisampled;
+    }
+
+    pub fn set_pixel_art(&mut self, pixel_art: bool) {
+        self.pixel_art = pixel_art;
+    }
+
+    // repeat tiling quietly wraps uvs outside [0, 1] back onto the texture, which hides bugs in whatever produced
+    // the draw data. in strict mode every texture is sampled with decal tiling so those texels come out transparent,
+    // and debug builds assert on the first out of range uv
+    pub fn set_strict_uv(&mut self, strict_uv: bool) {
+        self.strict_uv = strict_uv;
+    }
+
+    // a safety valve for generated or untrusted ui: once a render call has issued max_draw_calls draws it stops,
+    // leaving the rest of the frame undrawn and RendererError::DrawCallBudgetExceeded in last_error, rather than
+    // stalling on tens of thousands of commands. skipped and culled commands don't count. None is no limit
+    pub fn set_max_draw_calls(&mut self, max_draw_calls: Option<usize>) {
+        self.max_draw_calls = max_draw_calls;
+    }
+
+    // skips commands whose vertex colors are all fully transparent (spacers, elements faded all the way out)
+    // instead of drawing nothing with them. only done where that's provably a no-op: vertex colors combined
+    // with modulate or DstIn and a texture paint blending source over or plus, anything else still draws
+    pub fn set_skip_transparent(&mut self, skip_transparent: bool) {
+        self.skip_transparent = skip_transparent;
+    }
+
+    // untextured shapes (fills, borders, lines) are font commands whose uvs all sit on the atlas' white texel.
+    // those are drawn with this paint and no shader, so skia skips sampling the atlas and the color comes from
+    // the vertex colors combined with the paint's color under the font blend mode. the default is plain opaque
+    // white, which leaves the vertex colors as they are. only renderers that baked the atlas from a context
+    // (new, try_new, configure_fonts, reset) know where the white texel is, shared and captured atlases keep
+    // sampling it unless given its uv with set_white_pixel_uv
+    pub fn set_solid_paint(&mut self, solid_paint: skia_safe::Paint) {
+        self.solid_paint = solid_paint;
+    }
+
+    // where the atlas' white texel is for shared and captured atlases, the uv imgui reported when it baked them
+    // (font_white_pixel_uv). baking from a context sets this itself, None goes back to sampling the atlas
+    pub fn set_white_pixel_uv(&mut self, white_pixel_uv: Option<[f32; 2]>) {
+        self.white_pixel_uv = white_pixel_uv.map(|[white_u, white_v]| skia_safe::Point::new(white_u, white_v));
+    }
+
+    fn default_solid_paint() -> skia_safe::Paint
+    {
+        let mut solid_paint = skia_safe::Paint::default();
+        solid_paint.set_color(skia_safe::Color::WHITE);
+        solid_paint
+    }
+
+    fn is_solid_command(&self, texid: TextureId, uv: &[skia_safe::Point], cmd_idx: &[imgui::DrawIdx]) -> bool
+    {
+        match self.white_pixel_uv {
+            Some(white_pixel_uv) => self.is_font_texture(texid) && cmd_idx.iter().all(|index| uv[*index as usize] == white_pixel_uv),
+            None => false,
+        }
+    }
+
+    // debug aid: every command is filled with a solid color picked from its texture id instead of its real paint,
+    // so geometry drawn with the wrong texture stands out. text turns into solid quads over each glyph
+    pub fn set_debug_texture_colors(&mut self, debug_texture_colors: bool) {
+        self.debug_texture_colors = debug_texture_colors;
+    }
+
+    fn debug_texture_paint(texid: TextureId) -> skia_safe::Paint {
+        // stepping the hue by the golden ratio keeps neighbouring ids far apart on the color wheel
+        let hue = (texid.id() as f32 * 0.618_034).fract() * 360.0;
+        let color = skia_safe::HSV::from((hue, 0.85, 1.0)).to_color(255);
+
+        let mut paint = Paint::default();
+        paint.set_shader(skia_safe::shaders::color(color));
+
+        paint
+    }
+
+    // a copy of paint whose image shader draws nothing outside the image. skia doesn't hand back a shader's sampling
+    // so this is nearest, like every loader, even for textures given other sampling with set_texture_sampling
+    fn decal_paint(paint: &skia_safe::Paint) -> skia_safe::Paint {
+        let mut decal = paint.clone();
+        if let Some((image, local_matrix, _)) = paint.shader().and_then(|shader| shader.image()) {
+            let sampling_options = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Nearest, skia_safe::MipmapMode::None);
+            let tile_mode = skia_safe::TileMode::Decal;
+            decal.set_shader(image.to_shader((tile_mode, tile_mode), sampling_options, &local_matrix));
+        }
+
+        decal
+    }
+
+    // renders the ui into an offscreen layer that's composited with paint, so a color or image filter on the
+    // paint post-processes the whole ui at once rather than each command, None draws straight to the canvas
+    pub fn set_layer(&mut self, paint: Option<skia_safe::Paint>, bounds: Option<skia_safe::Rect>) {
+        self.layer_paint = paint;
+        self.layer_bounds = bounds;
+    }
+
+    // runs filter (a blur, a displacement map, ...) over the composited ui when the layer is restored, keeping
+    // whatever else the layer paint already carries. None removes the filter but leaves the layer in place,
+    // and without a layer it stays without one rather than paying for an empty layer every frame
+    // filters that reach outside their input, like blurs, can spill past the ui unless the layer has bounds
+    pub fn set_layer_image_filter(&mut self, filter: Option<skia_safe::ImageFilter>) {
+        if let Some(layer_paint) = &mut self.layer_paint {
+            layer_paint.set_image_filter(filter);
+        } else if let Some(filter) = filter {
+            let mut layer_paint = Paint::default();
+            layer_paint.set_image_filter(filter);
+            self.layer_paint = Some(layer_paint);
+        }
+    }
+
+    // fades the whole ui out through the mask's alpha (a vignette, a spotlight reveal), unlike a clip the edges
+    // can be soft. the mask is drawn once at the canvas origin at its own size, so make it as big as the frame
+    pub fn set_mask(&mut self, mask: Option<skia_safe::Image>) {
+        self.mask = mask;
+    }
+
+    // draws each draw list's opaque commands (opaque texture or solid fill, fully opaque vertex colors) before its translucent
+    // ones, keeping the order within each group. this changes the painter's order, so only turn it on when
+    // no opaque command is meant to cover a translucent one imgui drew before it
+    pub fn set_opaque_first(&mut self, opaque_first: bool) {
+        self.opaque_first = opaque_first;
+    }
+
+    // imgui submits in painter's order, which is what source over needs. additive textures (a paint with
+    // BlendMode::Plus) sum the same in any order though, so with this on each unbroken run of additive commands
+    // in a draw list is grouped by texture, saving texture switches for particle style ui. a command with any
+    // other blend mode ends the run and nothing moves across it, so the result is unchanged
+    pub fn set_group_additive(&mut self, group_additive: bool) {
+        self.group_additive = group_additive;
+    }
+
+    // for high contrast modes: on top of the normal fill, every solid shape (opaque vertex colors sampling a single
+    // texel, which is how imgui fills rects, frames and buttons) gets its outer edges stroked, centered on the edge
+    // text and images sample across their texture and are left alone. None turns it back off
+    pub fn set_outline(&mut self, outline: Option<Outline>) {
+        self.outline = outline;
+    }
+
+    pub fn set_font_blend_mode(&mut self, blend_mode: skia_safe::BlendMode) {
+        self.font_blend_mode = blend_mode;
+    }
+
+    // draw_vertices blends the atlas shader (src) with the vertex colors (dst). modulate multiplies all four
+    // channels, so glyphs only come out right while the atlas samples as white and any tint on the font
+    // paint leaks into the text. DstIn takes the color from the vertices and only coverage from the atlas,
+    // which is exactly the premultiplied source over wants for translucent text. false goes back to modulate
+    pub fn set_font_alpha_correction(&mut self, enabled: bool) {
+        self.font_blend_mode = if enabled { skia_safe::BlendMode::DstIn } else { skia_safe::BlendMode::Modulate };
+    }
+
+    // skia premultiplies both the texture and the vertex colors before blending them, and modulate of two
+    // premultiplied colors is itself premultiplied, so translucent windows drawn source over onto a transparent
+    // premultiplied surface leave the right alpha behind for compositing
+    pub fn set_image_blend_mode(&mut self, blend_mode: skia_safe::BlendMode) {
+        self.image_blend_mode = blend_mode;
+    }
+
+    // tints the whole ui, alpha included, by scaling every vertex color. the font atlas is white coverage
+    // modulated by the vertex color so text picks up the tint the same as fills and images do
+    pub fn set_global_tint(&mut self, color: skia_safe::Color) {
+        self.global_tint = color;
+    }
+
+    // at fractional dpi (1.25x, 1.5x) scaling the canvas leaves nearest sampled text straddling device pixels and blurry
+    // with this on, positions and clips are scaled by the draw data's framebuffer_scale (relative to display_pos) and
+    // then rounded to whole device pixels, so draw onto an unscaled canvas; off, they're passed through untouched
+    // in imgui's logical units and the canvas should be scaled by framebuffer_scale instead. never both
+    pub fn set_round_to_device_pixels(&mut self, round_to_device_pixels: bool) {
+        self.round_to_device_pixels = round_to_device_pixels;
+    }
+
+    // a scale on top of imgui's framebuffer_scale for apps that handle part of their dpi themselves (os zoom over
+    // an app zoom, say), the total is framebuffer_scale * device_pixel_ratio. framebuffer_scale is applied first,
+    // then the ratio, then with set_round_to_device_pixels the result is rounded, so the two only round once.
+    // with rounding off framebuffer_scale is still the canvas matrix's job and the ratio scales positions about
+    // display_pos in imgui's units beneath it. clips go through the same path as vertices so they scale together
+    // a ratio that isn't a positive finite number is rejected and the previous one kept
+    pub fn set_device_pixel_ratio(&mut self, device_pixel_ratio: f32) -> Result<(), RendererError> {
+        if !device_pixel_ratio.is_finite() || device_pixel_ratio <= 0.0 {
+            return Err(RendererError::InvalidDevicePixelRatio(device_pixel_ratio));
+        }
+
+        self.device_pixel_ratio = device_pixel_ratio;
+        Ok(())
+    }
+
+    fn device_point(&self, data: &DrawData, pos: [f32; 2]) -> skia_safe::Point {
+        let ratio = self.device_pixel_ratio;
+        if !self.round_to_device_pixels {
+            if ratio == 1.0 {
+                return skia_safe::Point::new(pos[0], pos[1]);
+            }
+
+            return skia_safe::Point::new(
+                data.display_pos[0] + (pos[0] - data.display_pos[0]) * ratio,
+                data.display_pos[1] + (pos[1] - data.display_pos[1]) * ratio,
+            );
+        }
+
+        skia_safe::Point::new(
+            ((pos[0] - data.display_pos[0]) * data.framebuffer_scale[0] * ratio).round(),
+            ((pos[1] - data.display_pos[1]) * data.framebuffer_scale[1] * ratio).round(),
+        )
+    }
+
+    // the area imgui laid the frame out in (display_pos to display_pos + display_size) in the coordinates we
+    // draw with, so device pixels with set_round_to_device_pixels and imgui's own units otherwise
+    // its width and height are the size of surface the frame needs, command clips are clamped to it
+    pub fn frame_extent(&self, data: &DrawData) -> skia_safe::Rect {
+        let min = self.device_point(data, data.display_pos);
+        let max = self.device_point(data, [data.display_pos[0] + data.display_size[0], data.display_pos[1] + data.display_size[1]]);
+
+        skia_safe::Rect::new(min.x, min.y, max.x, max.y)
+    }
+
+    // 8 bit channel multiply with rounding, 255 leaves the channel as it was
+    fn mul_channel(channel: u8, scale: u8) -> u8 {
+        ((channel as u16 * scale as u16 + 127) / 255) as u8
+    }
+
+    // the id the font atlas was registered under, new and configure_fonts point imgui's FontAtlas::tex_id at it
+    // a context shared with a renderer made another way (with_shared_font_atlas, say) needs tex_id set to this
+    pub fn font_texture_id(&self) -> TextureId {
+        self.font_texture_id
+    }
+
+    fn is_font_texture(&self, texture_id: TextureId) -> bool {
+        texture_id == self.font_texture_id
+    }
+
+    // render_imgui skips commands it can't draw rather than failing the frame, this hands back
+    // (and clears) the most recent reason one was skipped
+    pub fn take_last_error(&mut self) -> Option<RendererError> {
+        self.last_error.get_mut().take()
+    }
+
+    // stats for the most recent render call
+    pub fn frame_stats(&self) -> FrameStats {
+        self.frame_stats.borrow().clone()
+    }
+
+    fn clip_anti_alias(&self) -> bool {
+        !self.multisampled && !self.pixel_art
+    }
+
+    pub fn font_atlas_image(&self) -> &Arc<skia_safe::Image> {
+        &self.font_image
+    }
+
+    pub fn set_clip_op(&mut self, clip_op: skia_safe::ClipOp) {
+        self.clip_op = clip_op;
+    }
+
+    // every command's clip is intersected with this one, as if the whole frame were drawn inside a
+    // PushClipRect, for embedding the ui in a region of a larger canvas. it's in the same coordinates
+    // as the geometry and also bounds clip exempt commands. None leaves commands with just their own clip
+    pub fn set_outer_clip(&mut self, outer_clip: Option<skia_safe::Rect>) {
+        self.outer_clip = outer_clip;
+    }
+
+    pub fn set_clip_quality(&mut self, clip_quality: ClipQuality) {
+        self.clip_quality = clip_quality;
+    }
+
+    // Shared is the default, PerCommand is there to measure what sharing saves with FrameStats::clip_changes
+    pub fn set_clip_mode(&mut self, clip_mode: ClipMode) {
+        self.clip_mode = clip_mode;
+    }
+
+    fn apply_clip(&self, canvas: &mut skia_safe::Canvas, clip: skia_safe::Rect) {
+        // a rotated or skewed clip rect is no longer pixel aligned, skia's rect clip handles that but a path clip
+        // gets the full analytic coverage along the slanted edges
+        let axis_aligned = canvas.local_to_device_as_3x3().rect_stays_rect();
+        if self.clip_quality == ClipQuality::HighQuality && !axis_aligned {
+            canvas.clip_path(&skia_safe::Path::rect(clip, None), self.clip_op, true);
+        } else {
+            canvas.clip_rect(clip, self.clip_op, self.clip_anti_alias());
+        }
+    }
+
+    // lets the caller add any number of fonts to the atlas, the skia font paint is rebuilt once afterwards
+    pub fn configure_fonts<F>(&mut self, im_context: &mut Context, configure: F) -> Result<(), RendererError>
+    where
+        F: FnOnce(&mut imgui::FontAtlas),
+    {
+        // as in try_new, for renderers that weren't made from this context (reset onto a new one, a captured atlas)
+        im_context.io_mut().backend_flags.insert(imgui::BackendFlags::RENDERER_HAS_VTX_OFFSET);
+
+        let mut atlas = im_context.fonts();
+        configure(&mut atlas);
+
+        self.font_image = Arc::new(Self::build_font_image(&mut atlas)?);
+        self.font_config_hash = Some(Self::font_config_hash(&atlas));
+        let [white_u, white_v] = Self::atlas_white_pixel_uv(&atlas);
+        self.white_pixel_uv = Some(skia_safe::Point::new(white_u, white_v));
+
+        let mut font_paint = skia_safe::Paint::default();
+        Self::build_paint(&self.font_image, &Self::font_local_matrix(&self.font_image), &mut font_paint);
+
+        // the font keeps its texture id, so draw data from before the rebuild still resolves
+        let font_texture_id = self.font_texture_id;
+        atlas.tex_id = font_texture_id;
+        self.update_image(&font_texture_id, font_paint);
+
+        Ok(())
+    }
+
+    // true once fonts were added to or changed in the context since the renderer last built its atlas from it,
+    // reset (or configure_fonts) brings it back in line. renderers made from a shared or captured atlas never
+    // saw the context's fonts, so they always report true
+    pub fn needs_font_rebuild(&self, im_context: &mut Context) -> bool {
+        self.font_config_hash != Some(Self::font_config_hash(&im_context.fonts()))
+    }
+
+    // re-points the renderer at a new context by rebuilding the font atlas from it
+    // user textures and their ids survive, the font keeps its existing texture id so draw data from the new context still resolves
+    pub fn reset(&mut self, im_context: &mut Context) -> Result<(), RendererError> {
+        self.configure_fonts(im_context, |_| {})
+    }
+
+    pub fn render_imgui(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, )
+    {
+        self.render_draw_lists_where(canvas, data, |_, _| true);
+    }
+
+    // renders only the draw lists the predicate accepts, given each list's index in the draw data
+    // windows usually map 1:1 to draw lists so this is handy for per window thumbnails
+    pub fn render_draw_lists_where<P>(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, predicate: P)
+    where
+        P: FnMut(usize, &imgui::DrawList) -> bool,
+    {
+        self.render_lists(&mut [canvas], data, &mut FrameArena::default(), self.default_cursor_pass(), predicate);
+    }
+
+    // same as render_imgui but translates vertices into the caller's arena, so once it has grown to fit
+    // the ui a frame makes no allocations of its own. skia still allocates natively for each draw's vertices
+    pub fn render_imgui_with_arena(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, arena: &mut FrameArena)
+    {
+        self.render_lists(&mut [canvas], data, arena, self.default_cursor_pass(), |_, _| true);
+    }
+
+    // with io.mouse_draw_cursor on, imgui draws the cursor last into the foreground draw list (the last one)
+    // as four font textured quads, so enable this only then, otherwise the ui's last 24 indices go missing
+    // render_imgui then leaves the cursor out and render_software_cursor draws it, e.g. after everything else
+    pub fn set_defer_software_cursor(&mut self, defer_software_cursor: bool) {
+        self.defer_software_cursor = defer_software_cursor;
+    }
+
+    pub fn render_software_cursor(&self, canvas: &mut skia_safe::Canvas, data: &DrawData)
+    {
+        let last_list = data.draw_lists_count().saturating_sub(1);
+        self.render_lists(&mut [canvas], data, &mut FrameArena::default(), CursorPass::Only, |list_index, _| list_index == last_list);
+    }
+
+    // commands in an exempt draw list ignore their clip rect and are only clipped by the canvas itself, for
+    // overlays like drag previews that need to escape their window. imgui's clip is authoritative otherwise
+    pub fn set_clip_exempt_list(&mut self, list_index: usize, exempt: bool) {
+        if exempt {
+            self.clip_exempt_lists.insert(list_index);
+        } else {
+            self.clip_exempt_lists.remove(&list_index);
+        }
+    }
+
+    // same as set_clip_exempt_list but for every command drawing with texid, wherever it appears
+    pub fn set_clip_exempt_texture(&mut self, texid: TextureId, exempt: bool) {
+        if exempt {
+            self.clip_exempt_textures.insert(texid);
+        } else {
+            self.clip_exempt_textures.remove(&texid);
+        }
+    }
+
+    // glass and refraction: a command drawing texid first pulls in what's already on the canvas behind it, run
+    // through filter (a blur for frosted glass, a displacement map for refraction) and cut to the command's shape,
+    // then draws as usual on top, so texid is best a translucent tint. a RuntimeEffect can drive the distortion
+    // as the displacement map's source through image_filters::shader, but it can't sample the backdrop itself,
+    // skia-safe 0.50 has no runtime shader image filter. each flagged command costs two layers and a backdrop copy
+    pub fn set_backdrop_filter(&mut self, texid: TextureId, filter: Option<skia_safe::ImageFilter>) {
+        match filter {
+            Some(filter) => self.backdrop_filters.insert(texid, filter),
+            None => self.backdrop_filters.remove(&texid),
+        };
+    }
+
+    // lets the caller draw between ui layers, e.g. 3d content behind some windows and in front of others. before
+    // runs ahead of each rendered draw list and after once it's drawn, both with the list's index, on every
+    // canvas the call renders to. None leaves that side without a hook
+    pub fn set_draw_list_hooks(&mut self, before_draw_list: Option<DrawListHook>, after_draw_list: Option<DrawListHook>) {
+        self.before_draw_list = before_draw_list;
+        self.after_draw_list = after_draw_list;
+    }
+
+    fn default_cursor_pass(&self) -> CursorPass {
+        if self.defer_software_cursor { CursorPass::Exclude } else { CursorPass::Include }
+    }
+
+    // renders the same frame to every canvas, e.g. a main screen and a projector mirroring it
+    // vertices are translated and the skia geometry built once, then only the draws are repeated per canvas
+    pub fn render_imgui_multi(&self, canvases: &mut [&mut skia_safe::Canvas], data: &DrawData)
+    {
+        self.render_lists(canvases, data, &mut FrameArena::default(), self.default_cursor_pass(), |_, _| true);
+    }
+
+    // composites the draw data of several imgui contexts onto one canvas, in order, so later ones draw on top
+    // texture ids aren't namespaced per context, every context's ids are looked up in this renderer. contexts
+    // sharing one font atlas (Context::create_with_shared_font_atlas) just need its tex_id set to
+    // font_texture_id. a context with fonts of its own can have its atlas registered as an ordinary A8 image
+    // and its tex_id pointed at that id instead. frame_stats only cover the last draw data
+    pub fn render_multi_context(&self, canvas: &mut skia_safe::Canvas, draw_datas: &[&DrawData])
+    {
+        for data in draw_datas {
+            self.render_imgui(canvas, data);
+        }
+    }
+
+    fn render_lists<P>(&self, canvases: &mut [&mut skia_safe::Canvas], data: &DrawData, arena: &mut FrameArena, cursor_pass: CursorPass, mut predicate: P)
+    where
+        P: FnMut(usize, &imgui::DrawList) -> bool,
+    {
+        let mut frame_stats = self.frame_stats.borrow_mut();
+        frame_stats.reset();
+
+        // both go back into the arena at the end, so their capacity carries over to the next frame
+        let mut pass = self.begin_pass(canvases, std::mem::take(&mut arena.save_counts));
+        let mut commands = std::mem::take(&mut arena.commands);
+        let list_count = data.draw_lists_count();
+
+        for (list_index, draw_list) in data.draw_lists().enumerate() {
+            if !predicate(list_index, draw_list) {
+                continue;
+            }
+
+            self.run_draw_list_hook(&self.before_draw_list, canvases, &mut pass, list_index);
+
+            #[cfg(feature = "timing")]
+            let translate_start = std::time::Instant::now();
+
+            self.translate_list(data, draw_list, arena);
+            if self.prepare_commands(data, draw_list, list_index, list_index + 1 == list_count, cursor_pass, &mut commands) {
+                frame_stats.revalidated_lists += 1;
+            }
+            self.order_opaque_first(arena, &mut commands);
+            self.group_additive_runs(&mut commands);
+
+            #[cfg(feature = "timing")]
+            let draw_start = std::time::Instant::now();
+
+            self.draw_prepared(canvases, &mut pass, list_index, arena, &commands, &mut frame_stats);
+
+            #[cfg(feature = "timing")]
+            {
+                frame_stats.translate_time += draw_start - translate_start;
+                frame_stats.draw_time += draw_start.elapsed();
+            }
+
+            self.run_draw_list_hook(&self.after_draw_list, canvases, &mut pass, list_index);
+        }
+
+        arena.save_counts = self.end_pass(canvases, pass);
+        arena.commands = commands;
+    }
+
+    // translates the whole frame up front so it can be drawn any number of times with render_batch,
+    // e.g. to several targets, without going through imgui's buffers again
+    // textures and clip exemptions are looked up when the batch is drawn, vertex colors and the cursor pass are baked in
+    pub fn prepare(&self, data: &DrawData) -> GeometryBatch
+    {
+        let list_count = data.draw_lists_count();
+        let lists = data.draw_lists().enumerate().map(|(list_index, draw_list)| {
+            let mut geometry = FrameArena::default();
+            let mut commands = Vec::new();
+            self.translate_list(data, draw_list, &mut geometry);
+            self.prepare_commands(data, draw_list, list_index, list_index + 1 == list_count, self.default_cursor_pass(), &mut commands);
+            self.order_opaque_first(&geometry, &mut commands);
+            self.group_additive_runs(&mut commands);
+
+            PreparedList { list_index, geometry, commands }
+        });
+
+        GeometryBatch { lists: lists.collect() }
+    }
+
+    pub fn render_batch(&self, canvas: &mut skia_safe::Canvas, batch: &GeometryBatch)
+    {
+        let mut frame_stats = self.frame_stats.borrow_mut();
+        frame_stats.reset();
+
+        let canvases = &mut [canvas];
+        let mut pass = self.begin_pass(canvases, Vec::new());
+
+        #[cfg(feature = "timing")]
+        let draw_start = std::time::Instant::now();
+
+        for list in &batch.lists {
+            self.run_draw_list_hook(&self.before_draw_list, canvases, &mut pass, list.list_index);
+            self.draw_prepared(canvases, &mut pass, list.list_index, &list.geometry, &list.commands, &mut frame_stats);
+            self.run_draw_list_hook(&self.after_draw_list, canvases, &mut pass, list.list_index);
+        }
+
+        #[cfg(feature = "timing")]
+        {
+            frame_stats.draw_time = draw_start.elapsed();
+        }
+
+        self.end_pass(canvases, pass);
+    }
+
+    fn translate_list(&self, data: &DrawData, draw_list: &imgui::DrawList, arena: &mut FrameArena)
+    {
+        let FrameArena { idx, pos, uv, color, .. } = arena;
+        idx.clear();
+        pos.clear();
+        uv.clear();
+        color.clear();
+
+        // we've got to translate the vertex buffer from imgui into Skia friendly types
+        // thankfully skia_safe gives us a constructor for Color so we don't have to swizzle the colors as Skia expects BGR order
+        // there's no Color4f path here on purpose: imgui only gives us 8 bits per channel and SkVertices only accepts
+        // 8 bit SkColor, skia converts those from sRGB into the destination's (possibly wide gamut) color space itself
+        // that goes for text too, a color animated label is already quantized by imgui before we ever see it
+        for vertex in draw_list.vtx_buffer() {
+            pos.push(self.device_point(data, vertex.pos));
+
+            if self.strict_uv {
+                debug_assert!(
+                    (0.0..=1.0).contains(&vertex.uv[0]) && (0.0..=1.0).contains(&vertex.uv[1]),
+                    "uv {:?} is outside [0, 1]", vertex.uv
+                );
+            }
+
+            uv.push(skia_safe::Point {
+                x: vertex.uv[0],
+                y: vertex.uv[1]
+            });
+
+            let tint = self.global_tint;
+            color.push(skia_safe::Color::from_argb(
+                Self::mul_channel(vertex.col[3], tint.a()),
+                Self::mul_channel(vertex.col[0], tint.r()),
+                Self::mul_channel(vertex.col[1], tint.g()),
+                Self::mul_channel(vertex.col[2], tint.b()),
+            ));
+        }
+
+        // we build our index buffer
+        for index in draw_list.idx_buffer() {
+            idx.push(*index);
+        }
+    }
+
+    // resolves imgui's cmd buffer into the index ranges and device space clips we'll actually draw. returns
+    // whether the list's indices had to be checked, rather than matching the fingerprint of one that passed
+    fn prepare_commands(&self, data: &DrawData, draw_list: &imgui::DrawList, list_index: usize, is_last_list: bool, cursor_pass: CursorPass, commands: &mut Vec<PreparedCommand>) -> bool
+    {
+        commands.clear();
+
+        let fingerprint = Self::draw_list_fingerprint(draw_list);
+        let validated = self.validated_lists.borrow().get(&list_index) == Some(&fingerprint);
+        let mut all_valid = true;
+
+        let frame_extent = self.frame_extent(data);
+        let cmd_count = draw_list.commands().count();
+        for (cmd_index, cmd) in draw_list.commands().enumerate() {
+            match cmd {
+                imgui::DrawCmd::RawCallback {
+                    ..
+                } => {
+                    todo!("Raw callbacks unimplemented!")
+                }
+                imgui::DrawCmd::ResetRenderState => {
+                    todo!("Reset render state unimplemented!")
+                }
+                imgui::DrawCmd::Elements {
+                    count,
+                    cmd_params,
+                } => {
+                    // imgui emits clip rects in the same logical space as vertex positions, framebuffer_scale is
+                    // left to the backend. they go through device_point like the vertices do, so both are scaled
+                    // exactly once (or, with rounding off, both left for the canvas matrix to scale)
+                    let clip_rect = cmd_params.clip_rect;
+                    let clip_min = self.device_point(data, [clip_rect[0], clip_rect[1]]);
+                    let clip_max = self.device_point(data, [clip_rect[2], clip_rect[3]]);
+                    let mut skclip_rect = skia_safe::Rect::new(clip_min.x, clip_min.y, clip_max.x, clip_max.y);
+
+                    // imgui's clip rects can reach past the display (a window dragged half off screen), the canvas
+                    // may be larger than the frame so we clamp against the frame itself. only for intersect: with
+                    // difference the rect is a hole, and clamping it (or emptying it) would change what gets cut out
+                    if self.clip_op == skia_safe::ClipOp::Intersect && !skclip_rect.intersect(frame_extent) {
+                        skclip_rect = skia_safe::Rect::new_empty();
+                    }
+
+                    // the cursor adjustments below only narrow the range, so checking imgui's is enough
+                    if !validated && !Self::indices_in_range(draw_list, cmd_params.vtx_offset, cmd_params.idx_offset, count) {
+                        *self.last_error.borrow_mut() = Some(RendererError::InvalidIndices(list_index));
+                        all_valid = false;
+                        continue;
+                    }
+
+                    let mut idx_offset = cmd_params.idx_offset;
+                    let mut count = count;
+
+                    let holds_cursor = is_last_list && cmd_index + 1 == cmd_count && count >= SOFTWARE_CURSOR_INDICES;
+                    match cursor_pass {
+                        CursorPass::Include => {}
+                        CursorPass::Exclude => {
+                            if holds_cursor {
+                                count -= SOFTWARE_CURSOR_INDICES;
+                            }
+                        }
+                        CursorPass::Only => {
+                            if !holds_cursor {
+                                continue;
+                            }
+                            idx_offset += count - SOFTWARE_CURSOR_INDICES;
+                            count = SOFTWARE_CURSOR_INDICES;
+                        }
+                    }
+
+                    // skia would read an empty index buffer as "not indexed" and draw every vertex
+                    if count == 0 {
+                        continue;
+                    }
+
+                    commands.push(PreparedCommand {
+                        texture_id: cmd_params.texture_id,
+                        clip_rect: skclip_rect,
+                        vtx_offset: cmd_params.vtx_offset,
+                        idx_offset,
+                        count,
+                    });
+                }
+            }
+        }
+
+        if !validated {
+            let mut validated_lists = self.validated_lists.borrow_mut();
+            if all_valid {
+                validated_lists.insert(list_index, fingerprint);
+            } else {
+                validated_lists.remove(&list_index);
+            }
+        }
+
+        !validated
+    }
+
+    // what indices_in_range depends on: the vertex count, the indices, and each command's range into them
+    // a static ui resubmits the same lists every frame, and hashing this skips checking each index against
+    // the vertex count. a 64 bit collision with a list that passed would let a bad one through unchecked
+    fn draw_list_fingerprint(draw_list: &imgui::DrawList) -> u64
+    {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+
+        draw_list.vtx_buffer().len().hash(&mut hasher);
+        draw_list.idx_buffer().hash(&mut hasher);
+        for cmd in draw_list.commands() {
+            if let imgui::DrawCmd::Elements { count, cmd_params } = cmd {
+                (cmd_params.vtx_offset, cmd_params.idx_offset, count).hash(&mut hasher);
+            }
+        }
+
+        hasher.finish()
+    }
+
+    // skia copies whatever vertices the indices point at without checking them, so a corrupt or hand built draw
+    // list would have it read past the buffer. lists that pass are remembered by draw_list_fingerprint, so
+    // unchanged ones aren't checked again
+    fn indices_in_range(draw_list: &imgui::DrawList, vtx_offset: usize, idx_offset: usize, count: usize) -> bool
+    {
+        let idx_buffer = draw_list.idx_buffer();
+        let vtx_count = draw_list.vtx_buffer().len();
+
+        idx_offset.checked_add(count).is_some_and(|idx_end| idx_end <= idx_buffer.len())
+            && idx_buffer[idx_offset .. idx_offset + count].iter().all(|index| vtx_offset + (*index as usize) < vtx_count)
+    }
+
+    fn order_opaque_first(&self, geometry: &FrameArena, commands: &mut [PreparedCommand])
+    {
+        if self.opaque_first {
+            // sort_by_key is stable, so each group keeps imgui's order
+            commands.sort_by_key(|command| !self.is_opaque_command(geometry, command));
+        }
+    }
+
+    fn group_additive_runs(&self, commands: &mut [PreparedCommand])
+    {
+        if !self.group_additive {
+            return;
+        }
+
+        // sort_by_key is stable, so commands sharing a texture keep imgui's order within their run
+        for run in commands.split_mut(|command| !self.is_additive_command(command)) {
+            run.sort_by_key(|command| command.texture_id.id());
+        }
+    }
+
+    fn is_additive_command(&self, command: &PreparedCommand) -> bool
+    {
+        self.images.get(&command.texture_id.id()).is_some_and(|paint| paint.as_blend_mode() == Some(skia_safe::BlendMode::Plus))
+    }
+
+    // opaque when the texture is and so is every vertex color the command touches. fills and lines only sample
+    // the font atlas' white texel, which is drawn with the solid paint, so they count when that paint is opaque
+    fn is_opaque_command(&self, geometry: &FrameArena, command: &PreparedCommand) -> bool
+    {
+        let cmd_idx = &geometry.idx[command.idx_offset .. command.idx_offset + command.count];
+        let texture_opaque = self.texture_infos.get(&command.texture_id.id()).is_some_and(|info| info.opaque)
+            || (self.solid_paint.alpha() == 255 && self.is_solid_command(command.texture_id, &geometry.uv[command.vtx_offset..], cmd_idx));
+
+        texture_opaque && cmd_idx.iter().all(|index| geometry.color[command.vtx_offset + *index as usize].a() == 255)
+    }
+
+    fn begin_pass(&self, canvases: &mut [&mut skia_safe::Canvas], mut save_counts: Vec<(usize, usize)>) -> CanvasPass
+    {
+        save_counts.clear();
+
+        for canvas in canvases.iter_mut() {
+            // what an AutoCanvasRestore would do for a single canvas, each one is put back to its incoming save count
+            let restore_count = canvas.save_count();
+            canvas.save();
+
+            if let Some(layer_paint) = &self.layer_paint {
+                let mut layer_rec = skia_safe::canvas::SaveLayerRec::default().paint(layer_paint);
+                if let Some(layer_bounds) = &self.layer_bounds {
+                    layer_rec = layer_rec.bounds(layer_bounds);
+                }
+                canvas.save_layer(&layer_rec);
+            }
+
+            // the ui needs a layer of its own for the mask to cut into without touching what's under it
+            if self.mask.is_some() {
+                canvas.save_layer(&skia_safe::canvas::SaveLayerRec::default());
+            }
+
+            // applied below the per command clips, so those intersect with it instead of replacing it
+            if let Some(outer_clip) = self.outer_clip {
+                canvas.clip_rect(outer_clip, skia_safe::ClipOp::Intersect, self.clip_anti_alias());
+            }
+
+            save_counts.push((restore_count, canvas.save_count()));
+        }
+
+        CanvasPass {
+            save_counts,
+            current_clip: None,
+        }
+    }
+
+    // hooks draw without the last command's clip, but inside the ui's layers and outer clip, and whatever
+    // they leave saved or transformed is undone before the ui carries on
+    fn run_draw_list_hook(&self, hook: &Option<DrawListHook>, canvases: &mut [&mut skia_safe::Canvas], pass: &mut CanvasPass, list_index: usize)
+    {
+        let hook = match hook {
+            Some(hook) => hook,
+            None => return,
+        };
+
+        for (canvas, (_, base_save_count)) in canvases.iter_mut().zip(&pass.save_counts) {
+            canvas.restore_to_count(*base_save_count);
+            canvas.save();
+            hook(canvas, list_index);
+            canvas.restore_to_count(*base_save_count);
+        }
+        pass.current_clip = None;
+    }
+
+    // hands back the pass' save counts for the next one to reuse
+    fn end_pass(&self, canvases: &mut [&mut skia_safe::Canvas], pass: CanvasPass) -> Vec<(usize, usize)>
+    {
+        if let Some(mask) = &self.mask {
+            let mut mask_paint = Paint::default();
+            mask_paint.set_blend_mode(skia_safe::BlendMode::DstIn);
+
+            for (canvas, (_, base_save_count)) in canvases.iter_mut().zip(&pass.save_counts) {
+                // drop the last command's clip so the mask covers the whole layer
+                canvas.restore_to_count(*base_save_count);
+                canvas.draw_image(mask, skia_safe::Point::new(0.0, 0.0), Some(&mask_paint));
+            }
+        }
+
+        for (canvas, (restore_count, _)) in canvases.iter_mut().zip(&pass.save_counts) {
+            canvas.restore_to_count(*restore_count);
+        }
+
+        // inside begin_frame / end_frame every render call belongs to the same frame, end_frame moves it on
+        if !self.frame_open {
+            self.frame_number.set(self.frame_number.get() + 1);
+        }
+
+        pass.save_counts
+    }
+
+    // so now we've got to loop through the commands and draw everything with canvas.draw_vertices
+    // icon grids would be a natural fit for drawAtlas, but the skia-safe version we build against doesn't bind it
+    fn draw_prepared(&self, canvases: &mut [&mut skia_safe::Canvas], pass: &mut CanvasPass, list_index: usize, geometry: &FrameArena, commands: &[PreparedCommand], frame_stats: &mut FrameStats)
+    {
+        let FrameArena { idx, pos, uv, color, .. } = geometry;
+
+        let outline_paint = self.outline.map(|outline| {
+            let mut paint = Paint::default();
+            paint.set_color(outline.color);
+            paint.set_style(skia_safe::paint::Style::Stroke);
+            paint.set_stroke_width(outline.width);
+            paint.set_anti_alias(true);
+            paint
+        });
+
+        for command in commands {
+            if let Some(max_draw_calls) = self.max_draw_calls {
+                if frame_stats.draw_calls >= max_draw_calls {
+                    *self.last_error.borrow_mut() = Some(RendererError::DrawCallBudgetExceeded(max_draw_calls));
+                    return;
+                }
+            }
+
+            let id_index = command.texture_id;
+            let texture_paint = match self.texture_paint(id_index) {
+                Some(paint) => paint,
+                None if self.deferred_uploads.iter().any(|(deferred, _)| *deferred == id_index) => {
+                    *self.last_error.borrow_mut() = Some(RendererError::UploadDeferred(id_index));
+                    continue;
+                }
+                None => {
+                    *self.last_error.borrow_mut() = Some(RendererError::UnknownTexture(id_index));
+                    continue;
+                }
+            };
+
+            let vertex_mode = skia_safe::vertices::VertexMode::Triangles;
+            let vtx_offset = command.vtx_offset;
+            let cmd_idx = &idx[command.idx_offset .. command.idx_offset + command.count];
+            let solid = !self.debug_texture_colors && self.is_solid_command(id_index, &uv[vtx_offset..], cmd_idx);
+
+            let debug_paint;
+            let decal_paint;
+            let paint = if self.debug_texture_colors {
+                debug_paint = Self::debug_texture_paint(id_index);
+                &debug_paint
+            } else if solid {
+                &self.solid_paint
+            } else if self.strict_uv {
+                decal_paint = Self::decal_paint(&texture_paint);
+                &decal_paint
+            } else {
+                &*texture_paint
+            };
+
+            let blend_mode = if self.debug_texture_colors {
+                // the debug color alone, vertex colors and glyph coverage would only make textures harder to tell apart
+                skia_safe::BlendMode::Src
+            } else if self.is_font_texture(id_index) {
+                self.font_blend_mode
+            } else {
+                self.image_blend_mode
+            };
+
+            if self.skip_transparent && self.is_invisible_command(paint, id_index, blend_mode, &color[vtx_offset..], cmd_idx) {
+                continue;
+            }
+
+            let clip_exempt = self.clip_exempt_lists.contains(&list_index) || self.clip_exempt_textures.contains(&id_index);
+            let wanted_clip = if clip_exempt { None } else { Some(command.clip_rect) };
+
+            // clipped away entirely, e.g. a window outside the display. an empty difference clip cuts nothing out
+            let intersect = self.clip_op == skia_safe::ClipOp::Intersect;
+            if intersect && wanted_clip.is_some_and(|clip| clip.is_empty()) {
+                continue;
+            }
+
+            // or outside every canvas's own clip, e.g. away from the damage in render_imgui_clipped. a difference
+            // clip off the canvas keeps everything, so only intersect clips can be culled this way
+            if intersect && wanted_clip.is_some_and(|clip| canvases.iter().all(|canvas| canvas.quick_reject(&clip))) {
+                continue;
+            }
+
+            if self.clip_mode == ClipMode::PerCommand || pass.current_clip != wanted_clip {
+                for (canvas, (_, base_save_count)) in canvases.iter_mut().zip(&pass.save_counts) {
+                    canvas.restore_to_count(*base_save_count);
+                    if let Some(clip) = wanted_clip {
+                        canvas.save();
+                        self.apply_clip(canvas, clip);
+                    }
+                }
+                pass.current_clip = wanted_clip;
+                frame_stats.clip_changes += 1;
+            }
+            if let Some(clip) = wanted_clip {
+                frame_stats.record_clip(clip);
+            }
+
+            // indices are relative to vtx_offset, so skia only gets to see the vertices from there on
+            // without a shader the paint's color stands in for the texture, so the solid paint is alpha only too
+            let uniform_paint = Self::uniform_color_paint(paint, self.is_font_texture(id_index), blend_mode, &color[vtx_offset..], cmd_idx);
+            let vertices = if uniform_paint.is_some() {
+                frame_stats.uniform_color_draws += 1;
+                Self::vertices_without_colors(vertex_mode, &pos[vtx_offset..], &uv[vtx_offset..], cmd_idx)
+            } else {
+                skia_safe::Vertices::new_copy(vertex_mode, &pos[vtx_offset..], &uv[vtx_offset..], &color[vtx_offset..], Some(cmd_idx))
+            };
+            let paint = uniform_paint.as_ref().unwrap_or(paint);
+            if solid {
+                frame_stats.solid_draws += 1;
+            }
+
+            if let Some(backdrop) = self.backdrop_filters.get(&id_index) {
+                Self::draw_backdrop(canvases, backdrop, &vertices, Self::command_bounds(&pos[vtx_offset..], cmd_idx));
+            }
+
+            for canvas in canvases.iter_mut() {
+                canvas.draw_vertices(&vertices, blend_mode, paint);
+            }
+
+            if let Some(outline_paint) = &outline_paint {
+                if let Some(outline) = Self::solid_outline(geometry, vtx_offset, cmd_idx) {
+                    for canvas in canvases.iter_mut() {
+                        canvas.draw_path(&outline, outline_paint);
+                    }
+                }
+            }
+
+            frame_stats.record(id_index, &pos[vtx_offset..], cmd_idx);
+            self.texture_last_used.borrow_mut().insert(id_index.id(), self.frame_number.get());
+        }
+    }
+
+    // with modulate (or DstIn) the vertex alpha scales the result, so all zero alpha vertices draw a transparent
+    // source. source over and plus leave the destination alone for that, modes like Src or Clear wouldn't
+    fn is_invisible_command(&self, paint: &skia_safe::Paint, texid: TextureId, blend_mode: skia_safe::BlendMode, color: &[skia_safe::Color], cmd_idx: &[imgui::DrawIdx]) -> bool
+    {
+        let vertex_alpha_scales = matches!(blend_mode, skia_safe::BlendMode::Modulate | skia_safe::BlendMode::DstIn);
+        let leaves_dst = matches!(paint.as_blend_mode(), Some(skia_safe::BlendMode::SrcOver) | Some(skia_safe::BlendMode::Plus));
+
+        vertex_alpha_scales
+            && leaves_dst
+            && !self.backdrop_filters.contains_key(&texid)
+            && cmd_idx.iter().all(|index| color[*index as usize].a() == 0)
+    }
+
+    // the outer layer starts out as the filtered backdrop over the command's whole bounds. the command is drawn
+    // opaque white into a nested layer, which is merged back with DstIn, keeping just the part under its triangles
+    fn draw_backdrop(canvases: &mut [&mut skia_safe::Canvas], backdrop: &skia_safe::ImageFilter, vertices: &skia_safe::Vertices, bounds: skia_safe::Rect)
+    {
+        let mut shape = Paint::default();
+        shape.set_shader(skia_safe::shaders::color(skia_safe::Color::WHITE));
+
+        let mut cutout = Paint::default();
+        cutout.set_blend_mode(skia_safe::BlendMode::DstIn);
+
+        for canvas in canvases.iter_mut() {
+            canvas.save_layer(&skia_safe::canvas::SaveLayerRec::default().bounds(&bounds).backdrop(backdrop));
+            canvas.save_layer(&skia_safe::canvas::SaveLayerRec::default().bounds(&bounds).paint(&cutout));
+            canvas.draw_vertices(vertices, skia_safe::BlendMode::Src, &shape);
+            canvas.restore();
+            canvas.restore();
+        }
+    }
+
+    // vertices include the rest of the draw list from the command's vtx_offset, so their bounds would be too loose
+    fn command_bounds(pos: &[skia_safe::Point], cmd_idx: &[imgui::DrawIdx]) -> skia_safe::Rect
+    {
+        let mut bounds = skia_safe::Rect::new(f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for index in cmd_idx {
+            let point = pos[*index as usize];
+            bounds.left = bounds.left.min(point.x);
+            bounds.top = bounds.top.min(point.y);
+            bounds.right = bounds.right.max(point.x);
+            bounds.bottom = bounds.bottom.max(point.y);
+        }
+
+        bounds
+    }
+
+    // solid fills and single color text give every vertex of a command the same color. skia can then skip the
+    // per vertex colors and take the color from the paint, which comes out the same as modulate when either the
+    // texture is alpha only (the font atlas, tinted by the paint color) or the color is white and only its alpha
+    // matters. paints carrying a color of their own, and other blend modes, keep the per vertex path
+    fn uniform_color_paint(paint: &skia_safe::Paint, alpha_only: bool, blend_mode: skia_safe::BlendMode, color: &[skia_safe::Color], cmd_idx: &[imgui::DrawIdx]) -> Option<skia_safe::Paint>
+    {
+        if blend_mode != skia_safe::BlendMode::Modulate || paint.color() != skia_safe::Color::WHITE {
+            return None;
+        }
+
+        let uniform_color = color[*cmd_idx.first()? as usize];
+        if !cmd_idx.iter().all(|index| color[*index as usize] == uniform_color) {
+            return None;
+        }
+
+        let white = uniform_color.r() == 255 && uniform_color.g() == 255 && uniform_color.b() == 255;
+        if !alpha_only && !white {
+            return None;
+        }
+
+        let mut uniform_paint = paint.clone();
+        uniform_paint.set_color(uniform_color);
+
+        Some(uniform_paint)
+    }
+
+    fn vertices_without_colors(vertex_mode: skia_safe::vertices::VertexMode, pos: &[skia_safe::Point], uv: &[skia_safe::Point], cmd_idx: &[imgui::DrawIdx]) -> skia_safe::Vertices
+    {
+        let flags = skia_safe::vertices::BuilderFlags::HAS_TEX_COORDS;
+        let mut builder = skia_safe::vertices::Builder::new(vertex_mode, pos.len(), cmd_idx.len(), flags);
+        builder.positions().copy_from_slice(pos);
+        if let Some(tex_coords) = builder.tex_coords() {
+            tex_coords.copy_from_slice(uv);
+        }
+        if let Some(indices) = builder.indices() {
+            indices.copy_from_slice(cmd_idx);
+        }
+
+        builder.detach()
+    }
+
+    // the outer edges of a command's solid triangles. imgui shares vertices between the triangles of a shape, so
+    // an edge used by only one solid triangle is on the shape's boundary. anti-aliased fringes fade to transparent
+    // and so aren't solid, which puts the outline on the edge of the opaque core
+    fn solid_outline(geometry: &FrameArena, vtx_offset: usize, cmd_idx: &[imgui::DrawIdx]) -> Option<skia_safe::Path>
+    {
+        let vertex = |index: imgui::DrawIdx| vtx_offset + index as usize;
+        let mut edge_counts: HashMap<(imgui::DrawIdx, imgui::DrawIdx), u32> = HashMap::new();
+
+        for triangle in cmd_idx.chunks_exact(3) {
+            let first_uv = geometry.uv[vertex(triangle[0])];
+            let solid = triangle.iter().all(|index| {
+                geometry.color[vertex(*index)].a() == 255 && geometry.uv[vertex(*index)] == first_uv
+            });
+            if !solid {
+                continue;
+            }
+
+            for (a, b) in [(triangle[0], triangle[1]), (triangle[1], triangle[2]), (triangle[2], triangle[0])] {
+                *edge_counts.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+
+        let mut path = skia_safe::Path::new();
+        for ((a, b), _) in edge_counts.into_iter().filter(|(_, count)| *count == 1) {
+            path.move_to(geometry.pos[vertex(a)]);
+            path.line_to(geometry.pos[vertex(b)]);
+        }
+
+        if path.is_empty() { None } else { Some(path) }
+    }
+
+    // tag_of maps each draw list's index to a caller defined tag (a layer, a pass), and only lists tagged
+    // with tag are rendered, so ui layers can be interleaved with other drawing between calls
+    pub fn render_tagged<T, F>(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, mut tag_of: F, tag: &T)
+    where
+        T: PartialEq,
+        F: FnMut(usize, &imgui::DrawList) -> T,
+    {
+        self.render_draw_lists_where(canvas, data, |list_index, draw_list| tag_of(list_index, draw_list) == *tag);
+    }
+
+    // renders a frame into a fresh raster surface of the given size and encodes it as png, mostly for ui regression tests
+    pub fn render_to_png_bytes(&self, size: (i32, i32), data: &DrawData) -> Result<Vec<u8>, RendererError>
+    {
+        let mut surface = skia_safe::Surface::new_raster_n32_premul(size).ok_or(RendererError::SurfaceCreationFailed)?;
+        self.render_imgui(surface.canvas(), data);
+
+        let png = surface.image_snapshot()
+            .encode_to_data(skia_safe::EncodedImageFormat::PNG)
+            .ok_or(RendererError::EncodeFailed)?;
+
+        Ok(png.as_bytes().to_vec())
+    }
+
+    // renders straight into a tightly packed rgba buffer, width * 4 bytes a row, for headless output and software
+    // compositing. starts out transparent and comes back premultiplied, which is what compositors blend with
+    pub fn render_to_buffer(&self, width: i32, height: i32, data: &DrawData) -> Result<Vec<u8>, RendererError>
+    {
+        let info = skia_safe::ImageInfo::new((width, height), skia_safe::ColorType::RGBA8888, AlphaType::Premul, None);
+        let mut pixels = vec![0u8; info.compute_min_byte_size()];
+
+        {
+            let mut surface = skia_safe::Surface::new_raster_direct(&info, &mut pixels, None, None)
+                .ok_or(RendererError::SurfaceCreationFailed)?;
+            self.render_imgui(surface.canvas(), data);
+        }
+
+        Ok(pixels)
+    }
+
+    pub fn render_to_png(&self, size: (i32, i32), data: &DrawData, path: impl AsRef<Path>) -> Result<(), RendererError>
+    {
+        let png = self.render_to_png_bytes(size, data)?;
+        std::fs::write(path, png)?;
+
+        Ok(())
+    }
+
+    // renders the frame into an offscreen surface scale times the size the frame covers in device pixels and
+    // stretches that over the frame on canvas, for gpus that struggle with the ui at full resolution. scale is
+    // relative to the canvas' own matrix, so 1.0 on a 2x hidpi canvas is still full resolution. everything, text
+    // included, is resampled on the way up, so at 0.5 glyphs go soft with linear sampling or blocky with nearest
+    pub fn render_imgui_scaled(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, scale: f32, sampling: skia_safe::SamplingOptions) -> Result<(), RendererError>
+    {
+        // how far one unit along each axis reaches on the device, rotation and skew included
+        let device_matrix = canvas.local_to_device_as_3x3();
+        let device_scale_x = device_matrix.scale_x().hypot(device_matrix.skew_y());
+        let device_scale_y = device_matrix.skew_x().hypot(device_matrix.scale_y());
+        let (offscreen_scale_x, offscreen_scale_y) = (scale * device_scale_x, scale * device_scale_y);
+
+        let extent = self.frame_extent(data);
+        let size = ((extent.width() * offscreen_scale_x).ceil() as i32, (extent.height() * offscreen_scale_y).ceil() as i32);
+
+        // sharing the canvas' color space means colors are converted once, drawing into the offscreen surface
+        let info = skia_safe::ImageInfo::new_n32_premul(size, canvas.image_info().color_space());
+        let mut surface = Self::offscreen_surface(canvas, &info)?;
+
+        let offscreen = surface.canvas();
+        offscreen.scale((offscreen_scale_x, offscreen_scale_y));
+        offscreen.translate((-extent.left, -extent.top));
+        self.render_imgui(offscreen, data);
+
+        let image = surface.image_snapshot();
+        canvas.draw_image_rect_with_sampling_options(&image, None, extent, sampling, &Paint::default());
+
+        Ok(())
+    }
+
+    // a surface made from the canvas lives on the same gpu context, falling back to raster for canvases without one
+    // it's cleared here since a gpu surface starts out with whatever was in its memory
+    fn offscreen_surface(canvas: &mut skia_safe::Canvas, info: &skia_safe::ImageInfo) -> Result<skia_safe::Surface, RendererError>
+    {
+        let mut surface = canvas.new_surface(info, None)
+            .or_else(|| skia_safe::Surface::new_raster(info, None, None))
+            .ok_or(RendererError::SurfaceCreationFailed)?;
+        surface.canvas().clear(skia_safe::Color::TRANSPARENT);
+
+        Ok(surface)
+    }
+
+    // renders the frame clipped to the union of clips, device space rects such as a compositor's damage
+    // region, so only the damaged parts of the canvas get touched. commands whose clip rect falls outside
+    // the clip bounds are skipped before any geometry is built; ones landing in the gaps between rects
+    // are still clipped by skia rather than skipped, since that cull only looks at the bounds
+    pub fn render_imgui_clipped(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, clips: &[skia_safe::Rect])
+    {
+        let rects: Vec<skia_safe::IRect> = clips.iter().map(|clip| clip.round_out()).collect();
+        let mut region = skia_safe::Region::new();
+        region.set_rects(&rects);
+
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.clip_region(&region, skia_safe::ClipOp::Intersect);
+        self.render_imgui(&mut arc, data);
+    }
+
+    // supersampled anti-aliasing as a quality knob, independent of dpi scaling: the ui is rendered at factor times
+    // the resolution and filtered back down, smoothing text and edges past what the atlas and skia's coverage
+    // anti-aliasing manage. it costs factor squared the fill rate plus an offscreen surface that size every call
+    // 2 is the sweet spot, linear filtering at exactly half size averages each 2x2 block. larger factors go
+    // through mipmaps so they don't alias on the way down. the result lands on canvas at full resolution, covering
+    // the same pixels render_imgui would
+    pub fn render_imgui_supersampled(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, factor: f32) -> Result<(), RendererError>
+    {
+        let mipmap = if factor > 2.0 { skia_safe::MipmapMode::Linear } else { skia_safe::MipmapMode::None };
+        let sampling = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Linear, mipmap);
+
+        self.render_imgui_scaled(canvas, data, factor, sampling)
+    }
+
+    // walks every Elements command in the frame without drawing, in the order render_imgui would draw them
+    // clip rects are imgui's own, before any rounding or clamping the renderer applies
+    pub fn draw_commands(data: &DrawData) -> impl Iterator<Item = DrawCommandInfo> + '_
+    {
+        data.draw_lists().enumerate().flat_map(|(list_index, draw_list)| {
+            draw_list.commands().filter_map(move |cmd| match cmd {
+                imgui::DrawCmd::Elements { count, cmd_params } => Some(DrawCommandInfo {
+                    list_index,
+                    texture_id: cmd_params.texture_id,
+                    clip_rect: cmd_params.clip_rect,
+                    idx_offset: cmd_params.idx_offset,
+                    count,
+                    vtx_offset: cmd_params.vtx_offset,
+                }),
+                _ => None,
+            })
+        })
+    }
+
+    // the tight bounds of every vertex in the frame in framebuffer pixels, None when nothing is drawn. the device
+    // pixel ratio is included, so with one set the bounds cover the pixels render_imgui actually draws
+    pub fn content_bounds(&self, data: &DrawData) -> Option<skia_safe::Rect>
+    {
+        let mut bounds: Option<skia_safe::Rect> = None;
+
+        let ratio = self.device_pixel_ratio;
+        let scale = [data.framebuffer_scale[0] * ratio, data.framebuffer_scale[1] * ratio];
+        for draw_list in data.draw_lists() {
+            if let Some(list_bounds) = Self::draw_list_bounds(draw_list, data.display_pos, scale) {
+                bounds = Some(match bounds {
+                    Some(rect) => Self::union_rects(&rect, &list_bounds),
+                    None => list_bounds,
+                });
+            }
+        }
+
+        bounds
+    }
+
+    fn draw_list_bounds(draw_list: &imgui::DrawList, origin: [f32; 2], scale: [f32; 2]) -> Option<skia_safe::Rect>
+    {
+        let mut bounds: Option<skia_safe::Rect> = None;
+
+        for vertex in draw_list.vtx_buffer() {
+            let x = (vertex.pos[0] - origin[0]) * scale[0];
+            let y = (vertex.pos[1] - origin[1]) * scale[1];
+
+            bounds = Some(match bounds {
+                Some(rect) => skia_safe::Rect::new(rect.left.min(x), rect.top.min(y), rect.right.max(x), rect.bottom.max(y)),
+                None => skia_safe::Rect::new(x, y, x, y),
+            });
+        }
+
+        bounds
+    }
+
+    // Rect::join skips empty rects, which a single vertex or a straight line's bounds are
+    fn union_rects(a: &skia_safe::Rect, b: &skia_safe::Rect) -> skia_safe::Rect
+    {
+        skia_safe::Rect::new(a.left.min(b.left), a.top.min(b.top), a.right.max(b.right), a.bottom.max(b.bottom))
+    }
+
+    // a draw list's bounds in the canvas' device pixels, as render_imgui would draw it under matrix
+    fn device_list_bounds(&self, data: &DrawData, draw_list: &imgui::DrawList, matrix: &skia_safe::Matrix) -> Option<skia_safe::Rect>
+    {
+        let mut bounds: Option<skia_safe::Rect> = None;
+
+        for vertex in draw_list.vtx_buffer() {
+            let point = self.device_point(data, vertex.pos);
+            let point_rect = skia_safe::Rect::new(point.x, point.y, point.x, point.y);
+
+            bounds = Some(match bounds {
+                Some(rect) => Self::union_rects(&rect, &point_rect),
+                None => point_rect,
+            });
+        }
+
+        bounds.map(|rect| matrix.map_rect(rect).0)
+    }
+
+    // keeps the rendered ui in an offscreen surface between frames and only redraws the area covered by the
+    // draw lists in damaged_lists (their old and new bounds), a frame with no damage just composites the cached surface
+    // the ui is drawn into the surface under the canvas matrix and composited back at identity, so it's as sharp as
+    // drawing straight to the canvas. the cache is rebuilt from scratch when the canvas size or matrix, or the
+    // number of draw lists, changes
+    pub fn render_retained(&mut self, canvas: &mut skia_safe::Canvas, data: &DrawData, damaged_lists: &[usize]) -> Result<(), RendererError>
+    {
+        let info = canvas.image_info();
+        let matrix = canvas.local_to_device_as_3x3();
+        let list_bounds: Vec<Option<skia_safe::Rect>> = data.draw_lists()
+            .map(|draw_list| self.device_list_bounds(data, draw_list, &matrix))
+            .collect();
+
+        let mut retained = match self.retained.take() {
+            Some(mut retained) if retained.surface.width() == info.width()
+                && retained.surface.height() == info.height()
+                && retained.matrix == matrix
+                && retained.list_bounds.len() == list_bounds.len() => {
+                let mut damage: Option<skia_safe::Rect> = None;
+                for &list_index in damaged_lists {
+                    let old_and_new = [retained.list_bounds.get(list_index), list_bounds.get(list_index)];
+                    for rect in old_and_new.iter().flatten().filter_map(|rect| rect.as_ref()) {
+                        damage = Some(match damage {
+                            Some(damage) => Self::union_rects(&damage, rect),
+                            None => *rect,
+                        });
+                    }
+                }
+
+                if let Some(damage) = damage {
+                    // round out so the anti-aliased clip edge doesn't leave a faint seam of the old frame
+                    let damage = skia_safe::Rect::from_irect(damage.round_out());
+                    let retained_canvas = retained.surface.canvas();
+                    let mut arc = skia_safe::AutoCanvasRestore::guard(retained_canvas, true);
+                    arc.clip_rect(damage, skia_safe::ClipOp::Intersect, false);
+                    arc.clear(skia_safe::Color::TRANSPARENT);
+                    arc.concat(&matrix);
+
+                    // lists overlapping the damage have to be redrawn too, damaged or not, to layer correctly
+                    self.render_draw_lists_where(&mut arc, data, |list_index, _| {
+                        list_bounds[list_index].is_some_and(|rect| rect.intersects(damage))
+                    });
+                }
+
+                retained.list_bounds = list_bounds;
+                retained
+            }
+            _ => {
+                let mut surface = Self::offscreen_surface(canvas, &info)?;
+
+                {
+                    let mut arc = skia_safe::AutoCanvasRestore::guard(surface.canvas(), true);
+                    arc.concat(&matrix);
+                    self.render_imgui(&mut arc, data);
+                }
+
+                RetainedFrame {
+                    surface,
+                    matrix,
+                    list_bounds,
+                }
+            }
+        };
+
+        {
+            let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+            arc.reset_matrix();
+            arc.draw_image(retained.surface.image_snapshot(), (0.0, 0.0), None);
+        }
+        self.retained = Some(retained);
+
+        Ok(())
+    }
+
+    // draws the ui with its top left corner at dest_origin on the canvas, geometry and clips are both offset
+    pub fn render_imgui_at(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, dest_origin: skia_safe::Point)
+    {
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.translate(dest_origin);
+        self.render_imgui(&mut arc, data);
+    }
+
+    // rotates the whole ui by degrees (clockwise) about pivot, in the ui's own coordinates, for hud overlays
+    // clip rects are applied under the same matrix so they rotate with the geometry, skia clips to the rotated
+    // rect rather than its bounds. set_clip_quality(HighQuality) gives those slanted clip edges full coverage
+    pub fn render_imgui_rotated(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, degrees: f32, pivot: skia_safe::Point)
+    {
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.rotate(degrees, Some(pivot));
+        self.render_imgui(&mut arc, data);
+    }
+
+    // embeds the ui as a scrollable panel: its top left corner sits at panel_rect's, moved up and left by
+    // scroll_offset, and nothing is drawn outside panel_rect. commands scrolled out of view are skipped
+    pub fn render_panel(&self, canvas: &mut skia_safe::Canvas, data: &DrawData, panel_rect: skia_safe::Rect, scroll_offset: skia_safe::Vector)
+    {
+        let mut arc = skia_safe::AutoCanvasRestore::guard(canvas, true);
+        arc.clip_rect(panel_rect, skia_safe::ClipOp::Intersect, false);
+        arc.translate((panel_rect.left - scroll_offset.x, panel_rect.top - scroll_offset.y));
+        self.render_imgui(&mut arc, data);
+    }
+}
+
+// serializes the draw lists of a frame to json so broken frames can be inspected offline
+#[cfg(feature = "debug-dump")]
+pub fn dump_draw_data(data: &DrawData) -> String {
+    use serde_json::json;
+
+    let mut draw_lists = Vec::new();
+    for draw_list in data.draw_lists() {
+        let vertices: Vec<_> = draw_list.vtx_buffer().iter().map(|vertex| {
+            json!({
+                "pos": vertex.pos,
+                "uv": vertex.uv,
+                "col": vertex.col,
+            })
+        }).collect();
+
+        let commands: Vec<_> = draw_list.commands().map(|cmd| {
+            match cmd {
+                imgui::DrawCmd::Elements {
+                    count,
+                    cmd_params,
+                } => json!({
+                    "kind": "elements",
+                    "texture_id": cmd_params.texture_id.id(),
+                    "clip_rect": cmd_params.clip_rect,
+                    "vtx_offset": cmd_params.vtx_offset,
+                    "idx_offset": cmd_params.idx_offset,
+                    "count": count,
+                }),
+                imgui::DrawCmd::ResetRenderState => json!({ "kind": "reset_render_state" }),
+                imgui::DrawCmd::RawCallback { .. } => json!({ "kind": "raw_callback" }),
+            }
+        }).collect();
+
+        draw_lists.push(json!({
+            "vertices": vertices,
+            "indices": draw_list.idx_buffer(),
+            "commands": commands,
+        }));
+    }
+
+    json!({
+        "display_pos": data.display_pos,
+        "display_size": data.display_size,
+        "framebuffer_scale": data.framebuffer_scale,
+        "draw_lists": draw_lists,
+    }).to_string()
+}
+
+#[cfg(test)]
+mod tests;
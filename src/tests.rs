@@ -0,0 +1,2822 @@
+use super::*;
+use imgui::sys;
+use imgui::{DrawIdx, DrawVert};
+use std::alloc::{GlobalAlloc, Layout, System};
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const TRANSPARENT: [u8; 4] = [0, 0, 0, 0];
+
+// larger than any frame here, so commands are only clipped by the frame itself
+const UNCLIPPED: [f32; 4] = [-1000.0, -1000.0, 1000.0, 1000.0];
+
+// a 2x2 atlas of full coverage, font textured geometry draws in its vertex colors whatever its uvs
+fn test_renderer() -> Renderer {
+    Renderer::from_captured_font_atlas(&[0xFF; 4], 2, 2).unwrap()
+}
+
+// imgui keeps one current context per process, so tests that need a live one take turns
+fn context_lock() -> std::sync::MutexGuard<'static, ()> {
+    static CONTEXT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+    CONTEXT_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn test_context() -> Context {
+    let mut im_context = Context::create();
+    im_context.set_ini_filename(None);
+    im_context
+}
+
+fn with_context<R>(test: impl FnOnce(&mut Context) -> R) -> R {
+    let _guard = context_lock();
+    test(&mut test_context())
+}
+
+fn default_font(size_pixels: f32) -> [imgui::FontSource<'static>; 1] {
+    [imgui::FontSource::DefaultFontData { config: Some(imgui::FontConfig { size_pixels, ..Default::default() }) }]
+}
+
+// stands in for a bake that produced nothing. a failed bake leaves imgui's pixel pointer null, which imgui-rs
+// turns into a slice current rust rejects, so the atlas is handed an empty but valid texture instead. imgui
+// frees that pointer with the context, so it's taken back out before returning
+fn with_empty_atlas<R>(im_context: &mut Context, test: impl FnOnce(&mut Context) -> R) -> R {
+    static NO_PIXELS: u8 = 0;
+
+    let set_pixels = |im_context: &mut Context, pixels: *mut u8| {
+        let mut atlas = im_context.fonts();
+        let raw_atlas = unsafe { &mut *(&mut *atlas as *mut imgui::FontAtlas as *mut sys::ImFontAtlas) };
+        raw_atlas.TexPixelsAlpha8 = pixels;
+        raw_atlas.TexWidth = 0;
+        raw_atlas.TexHeight = 0;
+    };
+
+    set_pixels(im_context, &NO_PIXELS as *const u8 as *mut u8);
+    let result = test(im_context);
+    set_pixels(im_context, std::ptr::null_mut());
+
+    result
+}
+
+#[derive(Default)]
+struct TestList {
+    vertices: Vec<DrawVert>,
+    indices: Vec<DrawIdx>,
+    commands: Vec<sys::ImDrawCmd>,
+}
+
+// imgui only hands out DrawData from a live Context, and there's one global context per process, so frames
+// are built by hand instead. DrawData and DrawList are repr(C) mirrors of imgui's structs, which we point
+// at our own buffers the same way imgui points them at its own
+struct TestFrame {
+    lists: Vec<TestList>,
+    raw_lists: Vec<sys::ImDrawList>,
+    list_ptrs: Vec<*mut sys::ImDrawList>,
+    raw: sys::ImDrawData,
+    display_pos: [f32; 2],
+    display_size: [f32; 2],
+    framebuffer_scale: [f32; 2],
+}
+
+impl TestFrame {
+    fn new(width: f32, height: f32) -> Self {
+        TestFrame {
+            lists: Vec::new(),
+            raw_lists: Vec::new(),
+            list_ptrs: Vec::new(),
+            raw: sys::ImDrawData::default(),
+            display_pos: [0.0, 0.0],
+            display_size: [width, height],
+            framebuffer_scale: [1.0, 1.0],
+        }
+    }
+
+    fn add_list(&mut self) -> usize {
+        self.lists.push(TestList::default());
+        self.lists.len() - 1
+    }
+
+    // indices are relative to vertices and rebased onto the end of the list's vertex buffer
+    fn add_triangles(&mut self, list: usize, vertices: &[DrawVert], indices: &[DrawIdx], texture_id: TextureId, clip_rect: [f32; 4]) {
+        let base = self.lists[list].vertices.len() as DrawIdx;
+        let rebased: Vec<DrawIdx> = indices.iter().map(|index| base + index).collect();
+        self.push_command(list, vertices, &rebased, 0, texture_id, clip_rect);
+    }
+
+    // the command's vtx_offset points at its first vertex and the indices are left as they are, the way imgui
+    // splits a list once it goes past what 16 bit indices can reach
+    fn add_triangles_at_vtx_offset(&mut self, list: usize, vertices: &[DrawVert], indices: &[DrawIdx], texture_id: TextureId, clip_rect: [f32; 4]) {
+        let vtx_offset = self.lists[list].vertices.len();
+        self.push_command(list, vertices, indices, vtx_offset, texture_id, clip_rect);
+    }
+
+    fn push_command(&mut self, list: usize, vertices: &[DrawVert], indices: &[DrawIdx], vtx_offset: usize, texture_id: TextureId, clip_rect: [f32; 4]) {
+        let list = &mut self.lists[list];
+        let idx_offset = list.indices.len();
+        list.vertices.extend_from_slice(vertices);
+        list.indices.extend_from_slice(indices);
+        list.commands.push(sys::ImDrawCmd {
+            ClipRect: sys::ImVec4 { x: clip_rect[0], y: clip_rect[1], z: clip_rect[2], w: clip_rect[3] },
+            TextureId: texture_id.id() as *mut std::ffi::c_void,
+            VtxOffset: vtx_offset as u32,
+            IdxOffset: idx_offset as u32,
+            ElemCount: indices.len() as u32,
+            ..Default::default()
+        });
+    }
+
+    fn add_rect(&mut self, list: usize, rect: [f32; 4], color: [u8; 4], texture_id: TextureId, clip_rect: [f32; 4]) {
+        self.add_rect_uv(list, rect, [0.0, 0.0, 1.0, 1.0], color, texture_id, clip_rect);
+    }
+
+    fn add_rect_uv(&mut self, list: usize, rect: [f32; 4], uv_rect: [f32; 4], color: [u8; 4], texture_id: TextureId, clip_rect: [f32; 4]) {
+        self.add_triangles(list, &quad(rect, uv_rect, color), &QUAD_INDICES, texture_id, clip_rect);
+    }
+
+    fn draw_data(&mut self) -> &DrawData {
+        self.raw_lists = self.lists.iter_mut().map(|list| {
+            sys::ImDrawList {
+                CmdBuffer: sys::ImVector_ImDrawCmd {
+                    Size: list.commands.len() as i32,
+                    Capacity: list.commands.len() as i32,
+                    Data: list.commands.as_mut_ptr(),
+                },
+                IdxBuffer: sys::ImVector_ImDrawIdx {
+                    Size: list.indices.len() as i32,
+                    Capacity: list.indices.len() as i32,
+                    Data: list.indices.as_mut_ptr(),
+                },
+                VtxBuffer: sys::ImVector_ImDrawVert {
+                    Size: list.vertices.len() as i32,
+                    Capacity: list.vertices.len() as i32,
+                    Data: list.vertices.as_mut_ptr() as *mut sys::ImDrawVert,
+                },
+                ..Default::default()
+            }
+        }).collect();
+        self.list_ptrs = self.raw_lists.iter_mut().map(|raw_list| raw_list as *mut sys::ImDrawList).collect();
+
+        self.raw = sys::ImDrawData {
+            Valid: true,
+            CmdLists: self.list_ptrs.as_mut_ptr(),
+            CmdListsCount: self.lists.len() as i32,
+            TotalIdxCount: self.lists.iter().map(|list| list.indices.len() as i32).sum(),
+            TotalVtxCount: self.lists.iter().map(|list| list.vertices.len() as i32).sum(),
+            DisplayPos: sys::ImVec2 { x: self.display_pos[0], y: self.display_pos[1] },
+            DisplaySize: sys::ImVec2 { x: self.display_size[0], y: self.display_size[1] },
+            FramebufferScale: sys::ImVec2 { x: self.framebuffer_scale[0], y: self.framebuffer_scale[1] },
+        };
+
+        unsafe { &*(&self.raw as *const sys::ImDrawData as *const DrawData) }
+    }
+}
+
+const QUAD_INDICES: [DrawIdx; 6] = [0, 1, 2, 0, 2, 3];
+
+fn vertex(pos: [f32; 2], uv: [f32; 2], color: [u8; 4]) -> DrawVert {
+    DrawVert { pos, uv, col: color }
+}
+
+// clockwise from the top left, the way imgui winds its rects
+fn quad(rect: [f32; 4], uv_rect: [f32; 4], color: [u8; 4]) -> [DrawVert; 4] {
+    [
+        vertex([rect[0], rect[1]], [uv_rect[0], uv_rect[1]], color),
+        vertex([rect[2], rect[1]], [uv_rect[2], uv_rect[1]], color),
+        vertex([rect[2], rect[3]], [uv_rect[2], uv_rect[3]], color),
+        vertex([rect[0], rect[3]], [uv_rect[0], uv_rect[3]], color),
+    ]
+}
+
+fn rgba_image(texels: &[[u8; 4]]) -> Vec<u8> {
+    texels.iter().flatten().copied().collect()
+}
+
+// premultiplied rgba, the layout render_to_buffer hands back
+struct Pixels {
+    width: i32,
+    data: Vec<u8>,
+}
+
+impl Pixels {
+    fn at(&self, x: i32, y: i32) -> [u8; 4] {
+        let offset = (y * self.width + x) as usize * 4;
+        [self.data[offset], self.data[offset + 1], self.data[offset + 2], self.data[offset + 3]]
+    }
+}
+
+fn render(renderer: &Renderer, data: &DrawData, width: i32, height: i32) -> Pixels {
+    Pixels { width, data: renderer.render_to_buffer(width, height, data).unwrap() }
+}
+
+fn rgba_info(width: i32, height: i32) -> skia_safe::ImageInfo {
+    skia_safe::ImageInfo::new((width, height), skia_safe::ColorType::RGBA8888, AlphaType::Premul, None)
+}
+
+fn raster_surface(width: i32, height: i32) -> skia_safe::Surface {
+    let mut surface = skia_safe::Surface::new_raster(&rgba_info(width, height), None, None).unwrap();
+    surface.canvas().clear(skia_safe::Color::TRANSPARENT);
+    surface
+}
+
+fn read_pixels(surface: &mut skia_safe::Surface) -> Pixels {
+    let (width, height) = (surface.width(), surface.height());
+    let mut data = vec![0u8; width as usize * height as usize * 4];
+    assert!(surface.read_pixels(&rgba_info(width, height), &mut data, width as usize * 4, (0, 0)));
+
+    Pixels { width, data }
+}
+
+// a 2x2 image stretched over an 8x8 frame, each texel covers a 4x4 quadrant
+fn render_quadrants(paint: skia_safe::Paint) -> Pixels {
+    let mut renderer = test_renderer();
+    let texid = renderer.register_image(paint);
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], WHITE, texid, UNCLIPPED);
+
+    render(&renderer, frame.draw_data(), 8, 8)
+}
+
+fn assert_quadrants(pixels: &Pixels, expected: [[u8; 4]; 4]) {
+    assert_eq!(pixels.at(2, 2), expected[0]);
+    assert_eq!(pixels.at(6, 2), expected[1]);
+    assert_eq!(pixels.at(2, 6), expected[2]);
+    assert_eq!(pixels.at(6, 6), expected[3]);
+}
+
+#[test]
+fn strided_image_skips_row_padding() {
+    // three texels a row, the third is padding that must never be sampled
+    let img = rgba_image(&[RED, GREEN, BLUE, BLUE, WHITE, RED]);
+    let paint = Renderer::load_image_strided(&img, 2, 2, 12, Origin::TopLeft);
+
+    assert_quadrants(&render_quadrants(paint), [RED, GREEN, BLUE, WHITE]);
+}
+
+#[test]
+fn bottom_origin_image_samples_top_row_first() {
+    let img = rgba_image(&[BLUE, WHITE, RED, RED, GREEN, BLUE]);
+    let paint = Renderer::load_image_strided(&img, 2, 2, 12, Origin::BottomLeft);
+
+    assert_quadrants(&render_quadrants(paint), [RED, GREEN, BLUE, WHITE]);
+}
+
+#[cfg(feature = "debug-dump")]
+#[test]
+fn dump_draw_data_lists_vertices_and_commands() {
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [1.0, 2.0, 3.0, 4.0], RED, TextureId::new(7), [0.0, 0.0, 8.0, 8.0]);
+
+    let dump: serde_json::Value = serde_json::from_str(&dump_draw_data(frame.draw_data())).unwrap();
+
+    assert_eq!(dump["display_size"], serde_json::json!([8.0, 8.0]));
+    let draw_list = &dump["draw_lists"][0];
+    assert_eq!(draw_list["vertices"][0]["pos"], serde_json::json!([1.0, 2.0]));
+    assert_eq!(draw_list["vertices"][0]["col"], serde_json::json!(RED));
+    assert_eq!(draw_list["indices"], serde_json::json!(QUAD_INDICES));
+    assert_eq!(draw_list["commands"][0]["kind"], "elements");
+    assert_eq!(draw_list["commands"][0]["texture_id"], 7);
+    assert_eq!(draw_list["commands"][0]["count"], 6);
+    assert_eq!(draw_list["commands"][0]["clip_rect"], serde_json::json!([0.0, 0.0, 8.0, 8.0]));
+}
+
+#[test]
+fn color_keyed_texels_sample_transparent() {
+    let img = rgba_image(&[RED, GREEN, GREEN, RED]);
+    let paint = Renderer::load_image_rgba8_colorkey(&img, 2, 2, skia_safe::Color::from_rgb(255, 0, 0));
+
+    assert_quadrants(&render_quadrants(paint), [TRANSPARENT, GREEN, GREEN, TRANSPARENT]);
+}
+
+#[test]
+fn configure_fonts_bakes_the_atlas_once() {
+    with_context(|im_context| {
+        let mut renderer = Renderer::try_new(im_context).unwrap();
+        let font_texture_id = renderer.font_texture_id();
+
+        renderer.configure_fonts(im_context, |atlas| {
+            atlas.clear_fonts();
+            for size_pixels in &[13.0, 16.0, 20.0] {
+                atlas.add_font(&default_font(*size_pixels));
+                // adding a font throws away the bake, nothing is baked until the renderer builds the atlas
+                assert!(!atlas.is_built());
+            }
+        }).unwrap();
+
+        {
+            let atlas = im_context.fonts();
+            assert!(atlas.is_built());
+            assert_eq!(atlas.fonts().len(), 3);
+            assert_eq!(atlas.tex_id, font_texture_id);
+        }
+        assert!(!renderer.needs_font_rebuild(im_context));
+    });
+}
+
+#[test]
+fn render_imgui_at_offsets_geometry_and_clips() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(20.0, 20.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 20.0, 20.0], RED, renderer.font_texture_id(), [0.0, 0.0, 10.0, 10.0]);
+
+    let mut surface = raster_surface(200, 100);
+    renderer.render_imgui_at(surface.canvas(), frame.draw_data(), skia_safe::Point::new(100.0, 50.0));
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(105, 55), RED);
+    assert_eq!(pixels.at(5, 5), TRANSPARENT);
+    // the clip moved along with the geometry
+    assert_eq!(pixels.at(115, 65), TRANSPARENT);
+}
+
+// miri can't step into skia's ffi, so under `cargo miri test` only the view over the atlas bytes runs and the
+// renderer is covered by the regular run below. the view is the unsafe-adjacent part, it's what hands skia
+// a pointer and length into memory imgui owns
+#[test]
+fn font_texture_view_stays_within_the_atlas() {
+    let pixels = [0u8, 1, 2, 3, 4, 5, 6];
+    let view = FontTextureView::new(imgui::FontAtlasTexture { width: 3, height: 2, data: &pixels });
+
+    assert_eq!((view.width(), view.height()), (3, 2));
+    assert_eq!(view.data(), &pixels[..]);
+}
+
+#[test]
+#[should_panic(expected = "atlas buffer is too small")]
+fn font_texture_view_rejects_a_short_buffer() {
+    FontTextureView::from_a8(&[0u8; 5], 3, 2);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn renders_a_frame_from_a_font_texture_view() {
+    let image = FontTextureView::from_a8(&[0xFF; 9], 3, 3).to_image().unwrap();
+    let renderer = Renderer::with_shared_font_atlas(Arc::new(image));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+
+    assert_eq!(render(&renderer, frame.draw_data(), 4, 4).at(2, 2), GREEN);
+}
+
+#[test]
+fn commands_sharing_a_clip_reuse_it() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let window = [0.0, 0.0, 16.0, 16.0];
+    let child = [4.0, 4.0, 12.0, 12.0];
+
+    // a window's commands around a child window's, the way nested windows interleave in one list
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, font, window);
+    frame.add_rect(list, [1.0, 1.0, 15.0, 15.0], GREEN, font, window);
+    frame.add_rect(list, [4.0, 4.0, 12.0, 12.0], BLUE, font, child);
+    frame.add_rect(list, [5.0, 5.0, 11.0, 11.0], WHITE, font, child);
+    frame.add_rect(list, [0.0, 0.0, 2.0, 2.0], GREEN, font, window);
+
+    let pixels = render(&renderer, frame.draw_data(), 16, 16);
+    let stats = renderer.frame_stats();
+
+    // a clip per command would be 5
+    assert_eq!(stats.draw_calls, 5);
+    assert_eq!(stats.clip_changes, 3);
+    assert_eq!(stats.unique_clips, 2);
+
+    assert_eq!(pixels.at(1, 1), GREEN);
+    assert_eq!(pixels.at(3, 3), GREEN);
+    assert_eq!(pixels.at(4, 8), BLUE);
+    assert_eq!(pixels.at(8, 8), WHITE);
+}
+
+#[test]
+fn render_draw_lists_where_skips_rejected_lists() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let first = frame.add_list();
+    frame.add_rect(first, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let second = frame.add_list();
+    frame.add_rect(second, [4.0, 0.0, 8.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+
+    let mut surface = raster_surface(8, 4);
+    let mut seen = Vec::new();
+    renderer.render_draw_lists_where(surface.canvas(), frame.draw_data(), |list_index, _| {
+        seen.push(list_index);
+        list_index == second
+    });
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(seen, vec![first, second]);
+    assert_eq!(pixels.at(2, 2), TRANSPARENT);
+    assert_eq!(pixels.at(6, 2), GREEN);
+}
+
+#[test]
+fn vertex_colors_convert_into_a_linear_float_surface() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], [128, 128, 128, 255], renderer.font_texture_id(), UNCLIPPED);
+
+    let linear = skia_safe::ColorSpace::new_srgb_linear();
+    let info = skia_safe::ImageInfo::new((4, 4), skia_safe::ColorType::RGBAF16, AlphaType::Premul, linear.clone());
+    let mut surface = skia_safe::Surface::new_raster(&info, None, None).unwrap();
+    renderer.render_imgui(surface.canvas(), frame.draw_data());
+
+    let read_info = skia_safe::ImageInfo::new((4, 4), skia_safe::ColorType::RGBAF32, AlphaType::Premul, linear);
+    let mut data = vec![0u8; 4 * 4 * 16];
+    assert!(surface.read_pixels(&read_info, &mut data, 4 * 16, (0, 0)));
+
+    // sRGB 128 is about 0.216 in linear light, a surface fed the 8 bit value unconverted would hold 0.5
+    let texel = (2 * 4 + 2) * 16;
+    let red = f32::from_ne_bytes([data[texel], data[texel + 1], data[texel + 2], data[texel + 3]]);
+    assert!((red - 0.216).abs() < 0.01, "red channel was {}", red);
+}
+
+#[test]
+fn difference_clip_masks_out_the_clip_rect() {
+    let mut renderer = test_renderer();
+    renderer.set_clip_op(skia_safe::ClipOp::Difference);
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [2.0, 2.0, 6.0, 6.0]);
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+
+    assert_eq!(pixels.at(4, 4), TRANSPARENT);
+    assert_eq!(pixels.at(0, 0), RED);
+    assert_eq!(pixels.at(7, 4), RED);
+}
+
+#[test]
+fn renderers_share_one_font_atlas() {
+    let atlas = Renderer::captured_font_atlas(&[0xFF; 4], 2, 2).unwrap();
+    let first = Renderer::with_shared_font_atlas(atlas.clone());
+    let second = Renderer::with_shared_font_atlas(atlas.clone());
+
+    assert!(Arc::ptr_eq(first.font_atlas_image(), second.font_atlas_image()));
+    assert_eq!(Arc::strong_count(&atlas), 3);
+}
+
+#[test]
+fn content_bounds_covers_every_list_in_framebuffer_pixels() {
+    let mut renderer = test_renderer();
+    let mut frame = TestFrame::new(100.0, 100.0);
+    frame.display_pos = [10.0, 20.0];
+    frame.framebuffer_scale = [2.0, 2.0];
+    assert_eq!(renderer.content_bounds(frame.draw_data()), None);
+
+    let first = frame.add_list();
+    frame.add_rect(first, [12.0, 22.0, 16.0, 30.0], RED, TextureId::new(0), UNCLIPPED);
+    let second = frame.add_list();
+    frame.add_rect(second, [20.0, 25.0, 30.0, 26.0], RED, TextureId::new(0), UNCLIPPED);
+
+    assert_eq!(renderer.content_bounds(frame.draw_data()), Some(skia_safe::Rect::new(4.0, 4.0, 40.0, 20.0)));
+
+    // the device pixel ratio scales on top of framebuffer_scale
+    renderer.set_device_pixel_ratio(1.5).unwrap();
+    assert_eq!(renderer.content_bounds(frame.draw_data()), Some(skia_safe::Rect::new(6.0, 6.0, 60.0, 30.0)));
+}
+
+#[test]
+fn placeholder_shows_a_checkerboard_until_updated() {
+    let mut renderer = test_renderer();
+    let texid = renderer.register_placeholder();
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], WHITE, texid, UNCLIPPED);
+
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+    assert_eq!(pixels.at(0, 0), [255, 0, 255, 255]);
+    assert_eq!(pixels.at(1, 0), [0, 0, 0, 255]);
+    assert_eq!(pixels.at(1, 1), [255, 0, 255, 255]);
+
+    renderer.update_image(&texid, Renderer::load_image_rgba8(&GREEN, 1, 1));
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+    assert_eq!(pixels.at(0, 0), GREEN);
+    assert_eq!(pixels.at(1, 0), GREEN);
+    assert_eq!(renderer.texture_info(texid).map(|info| info.width), Some(1));
+}
+
+#[test]
+fn multisampled_hint_turns_off_clip_anti_aliasing() {
+    let mut renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [0.0, 0.0, 4.25, 8.0]);
+
+    let edge_alpha = |renderer: &Renderer, frame: &mut TestFrame| render(renderer, frame.draw_data(), 8, 8).at(4, 4)[3];
+
+    let anti_aliased = edge_alpha(&renderer, &mut frame);
+    assert!(anti_aliased > 0 && anti_aliased < 255, "edge alpha was {}", anti_aliased);
+
+    renderer.set_multisampled(true);
+    assert_eq!(edge_alpha(&renderer, &mut frame), 0);
+}
+
+// a 2x1 rgba png, a red texel then a blue one
+const RED_BLUE_PNG: [u8; 71] = [
+    0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+    0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x01, 0x08, 0x06, 0x00, 0x00, 0x00, 0xf4, 0x22, 0x7f,
+    0x8a, 0x00, 0x00, 0x00, 0x0e, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xcf, 0xc0, 0x00,
+    0x42, 0xff, 0x01, 0x0f, 0xf9, 0x03, 0xfd, 0x85, 0x11, 0x99, 0x76, 0x00, 0x00, 0x00, 0x00, 0x49,
+    0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+];
+
+#[test]
+fn encoded_png_decodes_to_its_pixels() {
+    let mut renderer = test_renderer();
+    let texid = renderer.register_image(Renderer::load_image_encoded(&RED_BLUE_PNG).unwrap());
+
+    let info = renderer.texture_info(texid).unwrap();
+    assert_eq!((info.width, info.height), (2, 1));
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], WHITE, texid, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), BLUE);
+}
+
+#[test]
+fn undecodable_bytes_are_an_error() {
+    assert!(matches!(Renderer::load_image_encoded(&RED_BLUE_PNG[..20]), Err(RendererError::DecodeFailed)));
+}
+
+#[test]
+fn render_tagged_draws_lists_with_the_matching_tag() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(12.0, 4.0);
+    for (list_index, color) in [RED, GREEN, BLUE].iter().enumerate() {
+        let list = frame.add_list();
+        let left = list_index as f32 * 4.0;
+        frame.add_rect(list, [left, 0.0, left + 4.0, 4.0], *color, renderer.font_texture_id(), UNCLIPPED);
+    }
+
+    let mut surface = raster_surface(12, 4);
+    let mut tagged = Vec::new();
+    renderer.render_tagged(surface.canvas(), frame.draw_data(), |list_index, _| {
+        tagged.push(list_index);
+        if list_index == 1 { "overlay" } else { "background" }
+    }, &"background");
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(tagged, vec![0, 1, 2]);
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), TRANSPARENT);
+    assert_eq!(pixels.at(10, 2), BLUE);
+}
+
+#[test]
+fn empty_font_atlas_is_an_error() {
+    with_context(|im_context| {
+        with_empty_atlas(im_context, |im_context| {
+            assert!(matches!(Renderer::try_new(im_context), Err(RendererError::EmptyFontAtlas)));
+        });
+    });
+
+    assert!(matches!(Renderer::captured_font_atlas(&[], 0, 0), Err(RendererError::EmptyFontAtlas)));
+}
+
+#[test]
+fn short_captured_font_atlas_is_an_error() {
+    assert!(matches!(Renderer::captured_font_atlas(&[0xFF; 3], 2, 2), Err(RendererError::InvalidImageSize(2, 2))));
+    assert!(matches!(Renderer::from_captured_font_atlas(&[0xFF; 3], 2, 2), Err(RendererError::InvalidImageSize(2, 2))));
+}
+
+#[test]
+fn pixel_art_magnifies_into_solid_blocks() {
+    let mut renderer = test_renderer();
+    renderer.set_pixel_art(true);
+
+    let texel = |x: i32, y: i32| [(x * 60) as u8, (y * 60) as u8, 255, 255];
+    let texels: Vec<[u8; 4]> = (0..4).flat_map(|y| (0..4).map(move |x| texel(x, y))).collect();
+    let texid = renderer.register_image(Renderer::load_image_pixel_art(&rgba_image(&texels), 4, 4));
+
+    let mut frame = TestFrame::new(32.0, 32.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 32.0, 32.0], WHITE, texid, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 32, 32);
+
+    // every pixel of each 8x8 block, edges included, is its texel's color with nothing blended in from neighbours
+    for y in 0..32 {
+        for x in 0..32 {
+            assert_eq!(pixels.at(x, y), texel(x / 8, y / 8), "pixel ({}, {})", x, y);
+        }
+    }
+}
+
+#[test]
+fn reset_keeps_user_textures() {
+    let _guard = context_lock();
+
+    let (mut renderer, texid) = {
+        let mut im_context = test_context();
+        let mut renderer = Renderer::try_new(&mut im_context).unwrap();
+        let texid = renderer.register_image(Renderer::load_image_rgba8(&GREEN, 1, 1));
+        (renderer, texid)
+    };
+
+    let mut im_context = test_context();
+    im_context.fonts().add_font(&default_font(20.0));
+    assert!(renderer.needs_font_rebuild(&mut im_context));
+
+    renderer.reset(&mut im_context).unwrap();
+    assert!(!renderer.needs_font_rebuild(&mut im_context));
+    assert_eq!(im_context.fonts().tex_id, renderer.font_texture_id());
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, texid, UNCLIPPED);
+    assert_eq!(render(&renderer, frame.draw_data(), 4, 4).at(2, 2), GREEN);
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn layer_paint_applies_to_the_composited_frame() {
+    let mut renderer = test_renderer();
+    let mut layer_paint = Paint::default();
+    layer_paint.set_alpha(128);
+    renderer.set_layer(Some(layer_paint), None);
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    let pixel = render(&renderer, frame.draw_data(), 4, 4).at(2, 2);
+
+    // faded once as a whole, the green hides the red. fading each command would let the red show through
+    assert_eq!(pixel[0], 0);
+    assert!((pixel[1] as i32 - 128).abs() <= 1 && (pixel[3] as i32 - 128).abs() <= 1, "pixel was {:?}", pixel);
+}
+
+#[test]
+fn layer_color_filter_recolors_every_command() {
+    let mut renderer = test_renderer();
+    let mut layer_paint = Paint::default();
+    layer_paint.set_color_filter(skia_safe::color_filters::blend(skia_safe::Color::BLUE, skia_safe::BlendMode::SrcIn));
+    renderer.set_layer(Some(layer_paint), None);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), BLUE);
+    assert_eq!(pixels.at(6, 2), BLUE);
+}
+
+#[test]
+fn white_pixel_samples_full_coverage() {
+    with_context(|im_context| {
+        let renderer = Renderer::try_new(im_context).unwrap();
+        let white_pixel_uv = Renderer::font_white_pixel_uv(im_context);
+
+        // a renderer on the same atlas that doesn't know the white texel, so it samples it through the font paint
+        // instead of switching to the solid paint
+        let sampling = Renderer::with_shared_font_atlas(renderer.font_atlas_image().clone());
+
+        let mut frame = TestFrame::new(4.0, 4.0);
+        let list = frame.add_list();
+        let uv_rect = [white_pixel_uv[0], white_pixel_uv[1], white_pixel_uv[0], white_pixel_uv[1]];
+        frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], uv_rect, WHITE, sampling.font_texture_id(), UNCLIPPED);
+
+        assert_eq!(render(&sampling, frame.draw_data(), 4, 4).at(2, 2), WHITE);
+        assert_eq!(sampling.frame_stats().solid_draws, 0);
+    });
+}
+
+#[test]
+fn mirrored_texture_reverses_its_columns() {
+    let mut renderer = test_renderer();
+    let mut mirror = skia_safe::Matrix::scale((-1.0, 1.0));
+    mirror.post_translate((1.0, 0.0));
+    let texid = renderer.register_image_transformed(&rgba_image(&[RED, GREEN, BLUE]), 3, 1, mirror);
+
+    let mut frame = TestFrame::new(12.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 12.0, 4.0], WHITE, texid, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 12, 4);
+
+    assert_eq!(pixels.at(2, 2), BLUE);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert_eq!(pixels.at(10, 2), RED);
+}
+
+// two lists side by side, the left one in left_color
+fn two_list_frame(font: TextureId, left_color: [u8; 4]) -> TestFrame {
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let left = frame.add_list();
+    frame.add_rect(left, [0.0, 0.0, 4.0, 4.0], left_color, font, UNCLIPPED);
+    let right = frame.add_list();
+    frame.add_rect(right, [4.0, 0.0, 8.0, 4.0], GREEN, font, UNCLIPPED);
+    frame
+}
+
+#[test]
+fn render_retained_redraws_only_damaged_lists() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let retained = |renderer: &mut Renderer, frame: &mut TestFrame, scale: f32, damaged_lists: &[usize]| {
+        let mut surface = raster_surface(16, 8);
+        surface.canvas().scale((scale, scale));
+        renderer.render_retained(surface.canvas(), frame.draw_data(), damaged_lists).unwrap();
+        read_pixels(&mut surface)
+    };
+
+    let pixels = retained(&mut renderer, &mut two_list_frame(font, RED), 2.0, &[]);
+    assert_eq!(pixels.at(4, 4), RED);
+    assert_eq!(pixels.at(12, 4), GREEN);
+
+    // nothing damaged, the cached frame is composited as it was and the recolored list isn't drawn
+    let mut recolored = two_list_frame(font, BLUE);
+    let pixels = retained(&mut renderer, &mut recolored, 2.0, &[]);
+    assert_eq!(pixels.at(4, 4), RED);
+    assert_eq!(pixels.at(12, 4), GREEN);
+
+    // the damage is in device pixels, so the scaled list's whole area is redrawn
+    let pixels = retained(&mut renderer, &mut recolored, 2.0, &[0]);
+    assert_eq!(pixels.at(1, 1), BLUE);
+    assert_eq!(pixels.at(7, 7), BLUE);
+    assert_eq!(pixels.at(12, 4), GREEN);
+
+    // a different canvas matrix starts over
+    let pixels = retained(&mut renderer, &mut two_list_frame(font, RED), 1.0, &[]);
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert_eq!(pixels.at(12, 4), TRANSPARENT);
+}
+
+#[test]
+fn per_texture_stats_sum_to_the_frame_totals() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&GREEN, 1, 1));
+
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, font, UNCLIPPED);
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], WHITE, image, UNCLIPPED);
+    frame.add_rect(list, [4.0, 4.0, 6.0, 6.0], BLUE, font, UNCLIPPED);
+    render(&renderer, frame.draw_data(), 16, 16);
+
+    let stats = renderer.frame_stats();
+    assert_eq!(stats.draw_calls, 3);
+    assert_eq!(stats.triangles, 6);
+    assert_eq!(stats.textures.values().map(|texture| texture.draw_calls).sum::<usize>(), stats.draw_calls);
+    assert_eq!(stats.textures.values().map(|texture| texture.vertices).sum::<usize>(), stats.vertices);
+    assert_eq!(stats.textures.values().map(|texture| texture.triangles).sum::<usize>(), stats.triangles);
+
+    assert_eq!(stats.textures[&font].draw_calls, 2);
+    assert_eq!(stats.textures[&font].covered_area, 16.0 + 4.0);
+    assert_eq!(stats.textures[&image].covered_area, 64.0);
+}
+
+// counts the allocations made on each thread, so tests running in parallel don't show up in each other's counts
+struct CountingAllocator;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        // try_with, the thread local may already be gone while a thread shuts down
+        let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn allocations() -> usize {
+    ALLOCATIONS.with(|allocations| allocations.get())
+}
+
+#[test]
+fn warm_arena_renders_without_allocating() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&GREEN, 1, 1));
+
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let window = frame.add_list();
+    frame.add_rect(window, [0.0, 0.0, 16.0, 8.0], RED, font, UNCLIPPED);
+    frame.add_rect(window, [2.0, 2.0, 6.0, 6.0], WHITE, image, [0.0, 0.0, 8.0, 8.0]);
+    let popup = frame.add_list();
+    frame.add_rect(popup, [0.0, 8.0, 16.0, 16.0], BLUE, font, [0.0, 8.0, 16.0, 16.0]);
+    let data = frame.draw_data();
+
+    let mut surface = raster_surface(16, 16);
+    let mut arena = FrameArena::with_capacity(16, 16);
+
+    // the first frame grows the arena and the renderer's bookkeeping to fit the ui
+    renderer.render_imgui_with_arena(surface.canvas(), data, &mut arena);
+
+    let before = allocations();
+    renderer.render_imgui_with_arena(surface.canvas(), data, &mut arena);
+    assert_eq!(allocations(), before);
+
+    assert_eq!(renderer.frame_stats().draw_calls, 3);
+}
+
+#[test]
+fn commands_index_from_their_vtx_offset() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, font, UNCLIPPED);
+    // indices starting from 0 again, only the vtx_offset puts this quad on the right
+    frame.add_triangles_at_vtx_offset(list, &quad([4.0, 0.0, 8.0, 4.0], [0.0, 0.0, 1.0, 1.0], GREEN), &QUAD_INDICES, font, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn configuring_fonts_advertises_vtx_offset_support() {
+    with_context(|im_context| {
+        let mut renderer = test_renderer();
+        assert!(!im_context.io().backend_flags.contains(imgui::BackendFlags::RENDERER_HAS_VTX_OFFSET));
+
+        renderer.reset(im_context).unwrap();
+        assert!(im_context.io().backend_flags.contains(imgui::BackendFlags::RENDERER_HAS_VTX_OFFSET));
+    });
+}
+
+#[test]
+fn render_to_png_bytes_encodes_a_png() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+
+    let png = renderer.render_to_png_bytes((4, 4), frame.draw_data()).unwrap();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+
+    let decoded = skia_safe::Image::from_encoded(skia_safe::Data::new_copy(&png)).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (4, 4));
+}
+
+#[test]
+fn font_and_image_commands_use_their_own_blend_modes() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1));
+    // src keeps the white atlas and drops the vertex color, dst keeps the vertex color and drops the image
+    renderer.set_font_blend_mode(skia_safe::BlendMode::Src);
+    renderer.set_image_blend_mode(skia_safe::BlendMode::Dst);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, font, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], RED, image, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), WHITE);
+    assert_eq!(pixels.at(6, 2), RED);
+}
+
+#[test]
+fn images_mut_tints_every_registered_paint() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+
+    let mut tinted = Vec::new();
+    for (texid, paint) in renderer.images_mut() {
+        paint.set_color_filter(skia_safe::color_filters::blend(skia_safe::Color::BLUE, skia_safe::BlendMode::SrcIn));
+        tinted.push(texid);
+    }
+    tinted.sort_by_key(|texid| texid.id());
+    assert_eq!(tinted, vec![font, image]);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, font, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, image, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), BLUE);
+    assert_eq!(pixels.at(6, 2), BLUE);
+}
+
+#[test]
+fn fractional_scale_rounds_positions_to_device_pixels() {
+    let mut renderer = test_renderer();
+    renderer.set_round_to_device_pixels(true);
+
+    let mut frame = TestFrame::new(6.0, 6.0);
+    frame.framebuffer_scale = [1.5, 1.5];
+    let list = frame.add_list();
+    // 1.5x puts these edges on 1.5, 4.95 and 7.5, all between device pixels
+    frame.add_rect(list, [1.0, 1.0, 3.3, 5.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+
+    let batch = renderer.prepare(data);
+    let positions = &batch.lists[0].geometry.pos;
+    assert_eq!(positions.len(), 4);
+    for pos in positions {
+        assert_eq!((pos.x.fract(), pos.y.fract()), (0.0, 0.0));
+    }
+    assert_eq!((positions[0].x, positions[0].y), (2.0, 2.0));
+    assert_eq!((positions[2].x, positions[2].y), (5.0, 8.0));
+
+    let pixels = render(&renderer, data, 9, 9);
+    assert_eq!(pixels.at(1, 1), TRANSPARENT);
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(4, 7), RED);
+    assert_eq!(pixels.at(5, 7), TRANSPARENT);
+}
+
+#[test]
+fn unknown_texture_sets_last_error_until_taken() {
+    let mut renderer = test_renderer();
+    let missing = TextureId::new(999);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, missing, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    // the bad command is skipped, the rest of the frame still draws
+    assert_eq!(pixels.at(2, 2), TRANSPARENT);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert!(matches!(renderer.take_last_error(), Some(RendererError::UnknownTexture(texid)) if texid == missing));
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn deferred_software_cursor_draws_separately() {
+    let mut renderer = test_renderer();
+    renderer.set_defer_software_cursor(true);
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let windows = frame.add_list();
+    frame.add_rect(windows, [0.0, 0.0, 8.0, 8.0], RED, font, UNCLIPPED);
+    // imgui's cursor is four quads (shadow, border, fill) at the end of the foreground list's last command
+    let foreground = frame.add_list();
+    let mut vertices = quad([4.0, 4.0, 8.0, 8.0], [0.0, 0.0, 1.0, 1.0], BLUE).to_vec();
+    let mut indices = QUAD_INDICES.to_vec();
+    for cursor_quad in 0..4 {
+        vertices.extend_from_slice(&quad([0.0, 0.0, 2.0, 2.0], [0.0, 0.0, 1.0, 1.0], GREEN));
+        indices.extend(QUAD_INDICES.iter().map(|index| index + 4 * (cursor_quad + 1)));
+    }
+    frame.add_triangles(foreground, &vertices, &indices, font, UNCLIPPED);
+    let data = frame.draw_data();
+
+    let mut surface = raster_surface(8, 8);
+    renderer.render_imgui(surface.canvas(), data);
+    let without_cursor = read_pixels(&mut surface);
+    assert_eq!(without_cursor.at(1, 1), RED);
+    assert_eq!(without_cursor.at(6, 6), BLUE);
+
+    let mut cursor_surface = raster_surface(8, 8);
+    renderer.render_software_cursor(cursor_surface.canvas(), data);
+    let cursor = read_pixels(&mut cursor_surface);
+    assert_eq!(cursor.at(1, 1), GREEN);
+    assert_eq!(cursor.at(6, 6), TRANSPARENT);
+
+    renderer.set_defer_software_cursor(false);
+    assert_eq!(render(&renderer, data, 8, 8).at(1, 1), GREEN);
+}
+
+#[test]
+fn texture_info_reports_each_format() {
+    let mut renderer = test_renderer();
+    let rgba = renderer.register_image(Renderer::load_image(&rgba_image(&[RED, GREEN]), 2, 1, PixelFormat::Rgba8888));
+    let bgra = renderer.register_image(Renderer::load_image(&rgba_image(&[BLUE, RED]), 1, 2, PixelFormat::Bgra8888));
+    let a8 = renderer.register_image(Renderer::load_image(&[0, 128, 255], 3, 1, PixelFormat::A8));
+    let gray = renderer.register_image(Renderer::load_image(&[64; 4], 2, 2, PixelFormat::Gray8));
+
+    let info = |texid| renderer.texture_info(texid).unwrap();
+    assert_eq!(info(renderer.font_texture_id()), TextureInfo { width: 2, height: 2, format: TextureFormat::A8, opaque: false });
+    assert_eq!(info(rgba), TextureInfo { width: 2, height: 1, format: TextureFormat::Rgba8, opaque: false });
+    assert_eq!(info(bgra), TextureInfo { width: 1, height: 2, format: TextureFormat::Bgra8, opaque: false });
+    assert_eq!(info(a8), TextureInfo { width: 3, height: 1, format: TextureFormat::A8, opaque: false });
+    assert_eq!(info(gray), TextureInfo { width: 2, height: 2, format: TextureFormat::Other(skia_safe::ColorType::Gray8), opaque: true });
+    assert_eq!(renderer.texture_info(TextureId::new(999)), None);
+}
+
+#[test]
+fn premultiplied_pixels_are_not_multiplied_twice() {
+    let half_red = [128, 0, 0, 128];
+    let premul = render_quadrants(Renderer::load_image_rgba8_premul(&rgba_image(&[half_red; 4]), 2, 2));
+    let straight = render_quadrants(Renderer::load_image_rgba8(&rgba_image(&[half_red; 4]), 2, 2));
+
+    assert_eq!(premul.at(4, 4), half_red);
+    // read as straight alpha the same bytes are a darker red at half coverage
+    assert_eq!(straight.at(4, 4), [64, 0, 0, 128]);
+}
+
+#[test]
+fn clip_exempt_commands_draw_outside_their_clip() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let overlay = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    renderer.set_clip_exempt_list(1, true);
+    renderer.set_clip_exempt_texture(overlay, true);
+
+    let tiny_clip = [0.0, 0.0, 1.0, 1.0];
+    let mut frame = TestFrame::new(16.0, 4.0);
+    let clipped = frame.add_list();
+    frame.add_rect(clipped, [0.0, 0.0, 8.0, 4.0], RED, font, [0.0, 0.0, 4.0, 4.0]);
+    let exempt_list = frame.add_list();
+    frame.add_rect(exempt_list, [8.0, 0.0, 12.0, 4.0], GREEN, font, tiny_clip);
+    let exempt_texture = frame.add_list();
+    frame.add_rect(exempt_texture, [12.0, 0.0, 16.0, 4.0], BLUE, overlay, tiny_clip);
+    let pixels = render(&renderer, frame.draw_data(), 16, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), TRANSPARENT);
+    assert_eq!(pixels.at(10, 2), GREEN);
+    assert_eq!(pixels.at(14, 2), BLUE);
+}
+
+#[test]
+fn captured_draw_data_replays_without_the_context() {
+    with_context(|im_context| {
+        im_context.io_mut().display_size = [320.0, 240.0];
+        let live = Renderer::try_new(im_context).unwrap();
+
+        // what a capture would have saved alongside the draw data
+        let (atlas_data, width, height) = {
+            let mut atlas = im_context.fonts();
+            let texture = atlas.build_alpha8_texture();
+            (texture.data.to_vec(), texture.width, texture.height)
+        };
+        let detached = Renderer::from_captured_font_atlas(&atlas_data, width, height).unwrap();
+        assert_eq!(detached.font_texture_id(), live.font_texture_id());
+
+        let ui = im_context.frame();
+        ui.text("replayed");
+        let data = ui.render();
+        assert!(data.total_idx_count > 0);
+
+        let replayed = render(&detached, data, 320, 240);
+        assert!(replayed.data.iter().any(|channel| *channel != 0));
+        assert_eq!(replayed.data, render(&live, data, 320, 240).data);
+    });
+}
+
+#[test]
+fn high_quality_clip_antialiases_under_rotation() {
+    let partial_coverage = |clip_quality| {
+        let mut renderer = test_renderer();
+        // with the target multisampled fast clips are aliased, so any partial coverage comes from the path clip
+        renderer.set_multisampled(true);
+        renderer.set_clip_quality(clip_quality);
+
+        let mut frame = TestFrame::new(32.0, 32.0);
+        let list = frame.add_list();
+        frame.add_rect(list, [0.0, 0.0, 32.0, 32.0], RED, renderer.font_texture_id(), [8.0, 8.0, 24.0, 24.0]);
+
+        let mut surface = raster_surface(32, 32);
+        renderer.render_imgui_rotated(surface.canvas(), frame.draw_data(), 30.0, skia_safe::Point::new(16.0, 16.0));
+        let pixels = read_pixels(&mut surface);
+
+        (0..32 * 32).filter(|i| (1..255).contains(&pixels.at(i % 32, i / 32)[3])).count()
+    };
+
+    assert_eq!(partial_coverage(ClipQuality::Fast), 0);
+    assert!(partial_coverage(ClipQuality::HighQuality) > 0);
+}
+
+#[test]
+fn render_imgui_multi_draws_the_same_frame_to_each_canvas() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [0.0, 0.0, 4.0, 8.0]);
+    frame.add_rect(list, [2.0, 2.0, 6.0, 6.0], BLUE, renderer.font_texture_id(), UNCLIPPED);
+
+    let mut main_screen = raster_surface(8, 8);
+    let mut projector = raster_surface(8, 8);
+    renderer.render_imgui_multi(&mut [main_screen.canvas(), projector.canvas()], frame.draw_data());
+    let main_pixels = read_pixels(&mut main_screen);
+
+    assert_eq!(main_pixels.at(1, 1), RED);
+    assert_eq!(main_pixels.at(6, 1), TRANSPARENT);
+    assert_eq!(main_pixels.at(5, 5), BLUE);
+    assert_eq!(main_pixels.data, read_pixels(&mut projector).data);
+}
+
+#[test]
+fn texture_memory_sums_every_texture() {
+    let mut renderer = test_renderer();
+    let font_bytes = 2 * 2;
+    assert_eq!(renderer.texture_memory_bytes(), font_bytes);
+
+    let rgba = renderer.register_image(Renderer::load_image_rgba8(&[0; 4 * 3 * 4], 4, 3));
+    renderer.register_image(Renderer::load_image(&[0; 8 * 2], 8, 2, PixelFormat::A8));
+    assert_eq!(renderer.texture_memory_bytes(), font_bytes + 4 * 3 * 4 + 8 * 2);
+
+    renderer.release_image(rgba);
+    assert_eq!(renderer.texture_memory_bytes(), font_bytes + 8 * 2);
+}
+
+#[test]
+fn global_tint_scales_text_and_images() {
+    let mut renderer = test_renderer();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    renderer.set_global_tint(skia_safe::Color::from_argb(255, 255, 128, 0));
+
+    let mut frame = TestFrame::new(12.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, renderer.font_texture_id(), UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, image, UNCLIPPED);
+    frame.add_rect(list, [8.0, 0.0, 12.0, 4.0], BLUE, image, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 12, 4);
+
+    assert_eq!(pixels.at(2, 2), [255, 128, 0, 255]);
+    assert_eq!(pixels.at(6, 2), [255, 128, 0, 255]);
+    // the tint has no blue, so blue scales to nothing and leaves black
+    assert_eq!(pixels.at(10, 2), [0, 0, 0, 255]);
+}
+
+#[test]
+fn prepared_batch_renders_to_several_canvases() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [0.0, 0.0, 8.0, 4.0]);
+    frame.add_rect(list, [0.0, 4.0, 4.0, 8.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+    let batch = renderer.prepare(data);
+
+    let mut first = raster_surface(8, 8);
+    let mut second = raster_surface(8, 8);
+    renderer.render_batch(first.canvas(), &batch);
+    renderer.render_batch(second.canvas(), &batch);
+    let first_pixels = read_pixels(&mut first);
+
+    assert_eq!(first_pixels.at(6, 2), RED);
+    assert_eq!(first_pixels.at(2, 6), GREEN);
+    assert_eq!(first_pixels.at(6, 6), TRANSPARENT);
+    assert_eq!(first_pixels.data, read_pixels(&mut second).data);
+    assert_eq!(first_pixels.data, render(&renderer, data, 8, 8).data);
+}
+
+#[test]
+fn odd_width_atlas_rows_stay_aligned() {
+    // five texels a row, which no row alignment skia might want lines up with
+    let atlas = [0, 0, 0, 0, 0xFF, 0xFF, 0, 0, 0, 0];
+    let image = FontTextureView::from_a8(&atlas, 5, 2).to_image().unwrap();
+
+    let mut read_back = [0u8; 10];
+    let info = skia_safe::ImageInfo::new_a8((5, 2));
+    assert!(image.read_pixels(&info, &mut read_back, 5, (0, 0), skia_safe::image::CachingHint::Allow));
+    assert_eq!(read_back, atlas);
+
+    let renderer = Renderer::from_captured_font_atlas(&atlas, 5, 2).unwrap();
+    let mut frame = TestFrame::new(5.0, 2.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 5.0, 2.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 5, 2);
+
+    assert_eq!(pixels.at(4, 0), RED);
+    assert_eq!(pixels.at(0, 1), RED);
+    assert_eq!(pixels.at(0, 0), TRANSPARENT);
+    assert_eq!(pixels.at(4, 1), TRANSPARENT);
+}
+
+#[test]
+fn surface_snapshot_registers_as_an_image() {
+    let mut minimap = raster_surface(2, 2);
+    minimap.canvas().clear(skia_safe::Color::GREEN);
+    let mut renderer = test_renderer();
+    let texid = renderer.register_surface_snapshot(&mut minimap);
+    // the snapshot is taken at registration, drawing afterwards doesn't reach the texture
+    minimap.canvas().clear(skia_safe::Color::RED);
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, texid, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 4, 4);
+
+    assert_eq!(pixels.at(2, 2), GREEN);
+    assert_eq!(renderer.texture_info(texid).map(|info| (info.width, info.height)), Some((2, 2)));
+}
+
+#[cfg(feature = "timing")]
+#[test]
+fn timing_fields_populate() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(64.0, 64.0);
+    let list = frame.add_list();
+    for i in 0..256 {
+        let (x, y) = ((i % 16) as f32 * 4.0, (i / 16) as f32 * 4.0);
+        frame.add_rect(list, [x, y, x + 4.0, y + 4.0], RED, renderer.font_texture_id(), [x, y, x + 4.0, y + 4.0]);
+    }
+    let data = frame.draw_data();
+
+    render(&renderer, data, 64, 64);
+    let stats = renderer.frame_stats();
+    assert!(stats.translate_time > std::time::Duration::ZERO);
+    assert!(stats.draw_time > std::time::Duration::ZERO);
+
+    let batch = renderer.prepare(data);
+    renderer.render_batch(raster_surface(64, 64).canvas(), &batch);
+    let stats = renderer.frame_stats();
+    assert_eq!(stats.translate_time, std::time::Duration::ZERO);
+    assert!(stats.draw_time > std::time::Duration::ZERO);
+}
+
+#[test]
+fn load_image_reads_each_pixel_format() {
+    let render_texel = |pixels: &[u8], format, color| {
+        let mut renderer = test_renderer();
+        let texid = renderer.register_image(Renderer::load_image(pixels, 1, 1, format));
+        let mut frame = TestFrame::new(4.0, 4.0);
+        let list = frame.add_list();
+        frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], color, texid, UNCLIPPED);
+        render(&renderer, frame.draw_data(), 4, 4).at(2, 2)
+    };
+
+    assert_eq!(render_texel(&[255, 0, 0, 255], PixelFormat::Rgba8888, WHITE), RED);
+    assert_eq!(render_texel(&[255, 0, 0, 255], PixelFormat::Bgra8888, WHITE), BLUE);
+    // coverage only, the color comes from the vertices like the font atlas
+    assert_eq!(render_texel(&[255], PixelFormat::A8, GREEN), GREEN);
+    assert_eq!(render_texel(&[0], PixelFormat::A8, GREEN), TRANSPARENT);
+    assert_eq!(render_texel(&[64], PixelFormat::Gray8, WHITE), [64, 64, 64, 255]);
+    // the fourth byte is ignored rather than read as alpha
+    assert_eq!(render_texel(&[10, 20, 30, 0], PixelFormat::Rgb888x, WHITE), [10, 20, 30, 255]);
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "is outside [0, 1]")]
+fn strict_uv_asserts_on_out_of_range_uvs() {
+    let mut renderer = test_renderer();
+    renderer.set_strict_uv(true);
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], [0.0, 0.0, 1.5, 1.0], WHITE, renderer.font_texture_id(), UNCLIPPED);
+    render(&renderer, frame.draw_data(), 4, 4);
+}
+
+#[test]
+fn layer_image_filter_blurs_the_ui() {
+    let render_edge = |filter: Option<skia_safe::ImageFilter>| {
+        let mut renderer = test_renderer();
+        renderer.set_layer_image_filter(filter);
+        let mut frame = TestFrame::new(16.0, 16.0);
+        let list = frame.add_list();
+        frame.add_rect(list, [8.0, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), UNCLIPPED);
+        render(&renderer, frame.draw_data(), 16, 16)
+    };
+
+    let sharp = render_edge(None);
+    assert_eq!(sharp.at(6, 8), TRANSPARENT);
+    assert_eq!(sharp.at(9, 8), RED);
+
+    let blurred = render_edge(skia_safe::image_filters::blur((2.0, 2.0), None, None, None));
+    // red bleeds out past the edge and the inside of the edge thins out
+    assert!(blurred.at(6, 8)[3] > 0);
+    assert!(blurred.at(9, 8)[3] < 255);
+}
+
+#[test]
+fn clearing_the_layer_image_filter_keeps_the_layer_as_it_was() {
+    let mut renderer = test_renderer();
+    renderer.set_layer_image_filter(None);
+    assert!(renderer.layer_paint.is_none());
+
+    let mut layer_paint = Paint::default();
+    layer_paint.set_alpha(128);
+    renderer.set_layer(Some(layer_paint), None);
+    renderer.set_layer_image_filter(skia_safe::image_filters::blur((2.0, 2.0), None, None, None));
+    renderer.set_layer_image_filter(None);
+    let layer_paint = renderer.layer_paint.as_ref().unwrap();
+    assert!(layer_paint.image_filter().is_none());
+    assert_eq!(layer_paint.alpha(), 128);
+}
+
+#[test]
+fn validate_draw_data_reports_unregistered_ids_once() {
+    let mut renderer = test_renderer();
+    let released = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    renderer.release_image(released);
+    let never_registered = TextureId::new(999);
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, renderer.font_texture_id(), UNCLIPPED);
+    assert_eq!(renderer.validate_draw_data(frame.draw_data()), Ok(()));
+
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, never_registered, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, released, UNCLIPPED);
+    frame.add_rect(list, [4.0, 4.0, 8.0, 8.0], WHITE, never_registered, UNCLIPPED);
+    assert_eq!(renderer.validate_draw_data(frame.draw_data()), Err(vec![never_registered, released]));
+}
+
+#[test]
+fn ninepatch_keeps_corners_and_stretches_the_rest() {
+    let mut renderer = test_renderer();
+    let img = rgba_image(&[
+        RED, GREEN, RED,
+        GREEN, BLUE, GREEN,
+        RED, GREEN, RED,
+    ]);
+    let texid = renderer.register_ninepatch(&img, 3, 3, Insets { left: 1, top: 1, right: 1, bottom: 1 });
+
+    let mut surface = raster_surface(12, 12);
+    renderer.draw_ninepatch(surface.canvas(), texid, skia_safe::Rect::new(0.0, 0.0, 12.0, 12.0)).unwrap();
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(0, 0), RED);
+    assert_eq!(pixels.at(11, 11), RED);
+    assert_eq!(pixels.at(1, 0), GREEN);
+    assert_eq!(pixels.at(6, 0), GREEN);
+    assert_eq!(pixels.at(0, 6), GREEN);
+    assert_eq!(pixels.at(1, 1), BLUE);
+    assert_eq!(pixels.at(6, 6), BLUE);
+
+    let plain = renderer.register_image(Renderer::load_image_rgba8(&img, 3, 3));
+    assert!(matches!(
+        renderer.draw_ninepatch(surface.canvas(), plain, skia_safe::Rect::new(0.0, 0.0, 12.0, 12.0)),
+        Err(RendererError::UnknownTexture(texid)) if texid == plain
+    ));
+}
+
+#[test]
+fn resolver_materializes_textures_on_first_reference() {
+    use std::sync::{Arc, Mutex};
+
+    // the resolver's own cache, keyed by the id imgui drew with
+    let materialized: Arc<Mutex<HashMap<usize, Vec<u8>>>> = Arc::default();
+    let materializations = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+
+    let mut renderer = test_renderer();
+    let (cache, count) = (materialized.clone(), materializations.clone());
+    renderer.set_texture_resolver(Some(Box::new(move |texid| {
+        if texid.id() < 1000 {
+            return None;
+        }
+
+        let mut cache = cache.lock().unwrap();
+        let pixels = cache.entry(texid.id()).or_insert_with(|| {
+            count.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            rgba_image(&[GREEN])
+        });
+        Some(Renderer::load_image_rgba8(pixels, 1, 1))
+    })));
+
+    let virtual_texture = TextureId::new(1000);
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, virtual_texture, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, TextureId::new(999), UNCLIPPED);
+    let data = frame.draw_data();
+    assert_eq!(materializations.load(std::sync::atomic::Ordering::SeqCst), 0);
+
+    for _ in 0..2 {
+        let pixels = render(&renderer, data, 8, 4);
+        assert_eq!(pixels.at(2, 2), GREEN);
+        assert_eq!(pixels.at(6, 2), TRANSPARENT);
+    }
+    assert_eq!(materializations.load(std::sync::atomic::Ordering::SeqCst), 1);
+    assert!(materialized.lock().unwrap().contains_key(&1000));
+    // the id the resolver turned down is skipped like any unknown texture
+    assert!(matches!(renderer.take_last_error(), Some(RendererError::UnknownTexture(texid)) if texid.id() == 999));
+}
+
+#[test]
+fn clips_clamp_to_display_size_not_the_canvas() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), [0.0, 0.0, 100.0, 100.0]);
+    let pixels = render(&renderer, frame.draw_data(), 16, 16);
+
+    assert_eq!(pixels.at(4, 4), RED);
+    assert_eq!(pixels.at(12, 4), TRANSPARENT);
+    assert_eq!(pixels.at(12, 12), TRANSPARENT);
+}
+
+#[test]
+fn difference_clips_are_not_clamped() {
+    let mut renderer = test_renderer();
+    renderer.set_clip_op(skia_safe::ClipOp::Difference);
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), [4.0, 4.0, 100.0, 100.0]);
+    let pixels = render(&renderer, frame.draw_data(), 16, 16);
+    // clamped to the frame the hole would stop at 8 and the corner would be drawn
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 6), TRANSPARENT);
+    assert_eq!(pixels.at(12, 12), TRANSPARENT);
+}
+
+#[test]
+fn translucent_text_composites_source_over() {
+    // half coverage everywhere, like the anti-aliased edge of a glyph
+    let mut renderer = Renderer::from_captured_font_atlas(&[0x80; 4], 2, 2).unwrap();
+    renderer.set_font_alpha_correction(true);
+    let background = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], BLUE, background, UNCLIPPED);
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], [255, 255, 255, 128], renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 4, 4);
+
+    // white at 50% alpha and 50% coverage over blue
+    let text_alpha = 128.0 / 255.0 * 128.0 / 255.0;
+    let expected = [255.0 * text_alpha, 255.0 * text_alpha, 255.0, 255.0];
+    for (channel, wanted) in pixels.at(2, 2).iter().zip(&expected) {
+        assert!((*channel as f32 - wanted).abs() <= 2.0, "{:?} isn't close to {:?}", pixels.at(2, 2), expected);
+    }
+}
+
+#[test]
+fn draw_commands_reports_command_metadata() {
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let first = frame.add_list();
+    frame.add_rect(first, [0.0, 0.0, 4.0, 4.0], RED, TextureId::new(0), [0.0, 0.0, 8.0, 8.0]);
+    frame.add_rect(first, [4.0, 0.0, 8.0, 4.0], RED, TextureId::new(3), [1.0, 2.0, 3.0, 4.0]);
+    let second = frame.add_list();
+    frame.add_triangles_at_vtx_offset(second, &quad([0.0, 0.0, 4.0, 4.0], [0.0, 0.0, 1.0, 1.0], RED), &QUAD_INDICES[..3], TextureId::new(5), UNCLIPPED);
+    frame.add_triangles_at_vtx_offset(second, &quad([4.0, 4.0, 8.0, 8.0], [0.0, 0.0, 1.0, 1.0], RED), &QUAD_INDICES, TextureId::new(5), UNCLIPPED);
+
+    let commands: Vec<DrawCommandInfo> = Renderer::draw_commands(frame.draw_data()).collect();
+    assert_eq!(commands, vec![
+        DrawCommandInfo { list_index: 0, texture_id: TextureId::new(0), clip_rect: [0.0, 0.0, 8.0, 8.0], idx_offset: 0, count: 6, vtx_offset: 0 },
+        DrawCommandInfo { list_index: 0, texture_id: TextureId::new(3), clip_rect: [1.0, 2.0, 3.0, 4.0], idx_offset: 6, count: 6, vtx_offset: 0 },
+        DrawCommandInfo { list_index: 1, texture_id: TextureId::new(5), clip_rect: UNCLIPPED, idx_offset: 0, count: 3, vtx_offset: 0 },
+        DrawCommandInfo { list_index: 1, texture_id: TextureId::new(5), clip_rect: UNCLIPPED, idx_offset: 3, count: 6, vtx_offset: 4 },
+    ]);
+}
+
+#[test]
+fn updates_during_a_frame_wait_for_end_frame() {
+    let mut renderer = test_renderer();
+    let texid = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, texid, UNCLIPPED);
+    let data = frame.draw_data();
+
+    renderer.begin_frame();
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), RED);
+    renderer.update_image(&texid, Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1));
+    renderer.update_image(&texid, Renderer::load_image_rgba8(&rgba_image(&[BLUE]), 1, 1));
+    // the rest of the in flight frame still sees the texture it started with
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), RED);
+    renderer.end_frame();
+
+    // applied in order, so the last update wins
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), BLUE);
+
+    // outside a frame updates land straight away
+    renderer.update_image(&texid, Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1));
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), GREEN);
+}
+
+#[test]
+fn ninepatches_last_until_their_update_lands() {
+    let mut renderer = test_renderer();
+    let img = rgba_image(&[
+        RED, GREEN, RED,
+        GREEN, BLUE, GREEN,
+        RED, GREEN, RED,
+    ]);
+    let texid = renderer.register_ninepatch(&img, 3, 3, Insets { left: 1, top: 1, right: 1, bottom: 1 });
+    let dst_rect = skia_safe::Rect::new(0.0, 0.0, 12.0, 12.0);
+
+    renderer.begin_frame();
+    renderer.update_image(&texid, Renderer::load_image_rgba8(&img, 3, 3));
+    // the rest of the frame still draws the ninepatch
+    let mut surface = raster_surface(12, 12);
+    renderer.draw_ninepatch(surface.canvas(), texid, dst_rect).unwrap();
+    let pixels = read_pixels(&mut surface);
+    assert_eq!(pixels.at(0, 0), RED);
+    assert_eq!(pixels.at(6, 6), BLUE);
+    renderer.end_frame();
+
+    // the plain replacement has no insets
+    assert!(matches!(
+        renderer.draw_ninepatch(surface.canvas(), texid, dst_rect),
+        Err(RendererError::UnknownTexture(unknown)) if unknown == texid
+    ));
+}
+
+#[test]
+fn radial_mask_fades_the_edges_out() {
+    // alpha falling off linearly from the center to nothing 8 pixels out
+    let mut mask_alpha = Vec::new();
+    for y in 0..16 {
+        for x in 0..16 {
+            let distance = ((x as f32 + 0.5 - 8.0).powi(2) + (y as f32 + 0.5 - 8.0).powi(2)).sqrt();
+            mask_alpha.push((255.0 * (1.0 - distance / 8.0)).clamp(0.0, 255.0) as u8);
+        }
+    }
+    let mask = FontTextureView::from_a8(&mask_alpha, 16, 16).to_image().unwrap();
+
+    let mut renderer = test_renderer();
+    renderer.set_mask(Some(mask));
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 16, 16);
+
+    let alpha = |x, y| pixels.at(x, y)[3];
+    assert!(alpha(8, 8) > 200);
+    assert!(alpha(12, 8) > 0 && alpha(12, 8) < alpha(8, 8));
+    assert_eq!(pixels.at(0, 0), TRANSPARENT);
+    assert_eq!(pixels.at(15, 15), TRANSPARENT);
+}
+
+#[test]
+fn opaque_commands_draw_first() {
+    let mut renderer = test_renderer();
+    renderer.set_opaque_first(true);
+    let overlay = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    let backdrop = renderer.register_image(Renderer::load_image(&[255, 255, 255, 0], 1, 1, PixelFormat::Rgb888x));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], [0, 255, 0, 128], overlay, UNCLIPPED);
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, backdrop, UNCLIPPED);
+    let data = frame.draw_data();
+
+    let batch = renderer.prepare(data);
+    let order: Vec<TextureId> = batch.lists[0].commands.iter().map(|command| command.texture_id).collect();
+    assert_eq!(order, vec![backdrop, overlay]);
+
+    // the opaque backdrop moved under the translucent overlay instead of covering it
+    let pixel = render(&renderer, data, 4, 4).at(2, 2);
+    assert_ne!(pixel, RED);
+    assert!(pixel[0] > 0 && pixel[1] > 0 && pixel[3] == 255);
+}
+
+#[test]
+fn renderer_from_synthetic_atlas_bytes_draws_its_coverage() {
+    let renderer = Renderer::from_captured_font_atlas(&[0xFF, 0, 0, 0xFF], 2, 2).unwrap();
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+
+    assert_quadrants(&pixels, [RED, TRANSPARENT, TRANSPARENT, RED]);
+    assert!(matches!(Renderer::from_captured_font_atlas(&[], 0, 0), Err(RendererError::EmptyFontAtlas)));
+}
+
+#[test]
+fn nested_clips_intersect() {
+    let mut renderer = test_renderer();
+    renderer.set_outer_clip(Some(skia_safe::Rect::new(0.0, 0.0, 6.0, 16.0)));
+
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), [4.0, 0.0, 12.0, 12.0]);
+
+    let mut surface = raster_surface(16, 16);
+    // the caller's own clip on the canvas is the outermost one
+    surface.canvas().clip_rect(skia_safe::Rect::new(0.0, 0.0, 16.0, 5.0), skia_safe::ClipOp::Intersect, false);
+    renderer.render_imgui(surface.canvas(), frame.draw_data());
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(5, 2), RED);
+    assert_eq!(pixels.at(2, 2), TRANSPARENT); // outside the command's clip
+    assert_eq!(pixels.at(8, 2), TRANSPARENT); // outside the outer clip
+    assert_eq!(pixels.at(5, 8), TRANSPARENT); // outside the canvas clip
+}
+
+#[test]
+fn gradient_tinted_text_interpolates_smoothly() {
+    let renderer = test_renderer();
+    let black = [0, 0, 0, 255];
+
+    // a label tinted from black on the left to red on the right
+    let mut frame = TestFrame::new(256.0, 1.0);
+    let list = frame.add_list();
+    let vertices = [
+        vertex([0.0, 0.0], [0.0, 0.0], black),
+        vertex([256.0, 0.0], [1.0, 0.0], RED),
+        vertex([256.0, 1.0], [1.0, 1.0], RED),
+        vertex([0.0, 1.0], [0.0, 1.0], black),
+    ];
+    frame.add_triangles(list, &vertices, &QUAD_INDICES, renderer.font_texture_id(), UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 256, 1);
+
+    // skia interpolates between the vertex colors at full precision, so the ramp only steps one level at a time
+    let reds: Vec<u8> = (0..256).map(|x| pixels.at(x, 0)[0]).collect();
+    assert!(reds.windows(2).all(|pair| pair[1] >= pair[0] && pair[1] - pair[0] <= 2));
+    assert!(reds[0] <= 2 && reds[255] >= 253);
+}
+
+#[test]
+fn needs_font_rebuild_flips_until_rebuilt() {
+    with_context(|im_context| {
+        let mut renderer = Renderer::try_new(im_context).unwrap();
+        assert!(!renderer.needs_font_rebuild(im_context));
+
+        im_context.fonts().add_font(&default_font(24.0));
+        assert!(renderer.needs_font_rebuild(im_context));
+        // asking doesn't rebuild anything
+        assert!(renderer.needs_font_rebuild(im_context));
+
+        renderer.configure_fonts(im_context, |_| {}).unwrap();
+        assert!(!renderer.needs_font_rebuild(im_context));
+    });
+}
+
+#[test]
+fn effect_texture_renders_its_time_uniform() {
+    let effect = skia_safe::RuntimeEffect::make_for_shader(
+        "uniform float time; half4 main(float2 coord) { return half4(time, 0, 1 - time, 1); }",
+        None,
+    ).unwrap();
+
+    let mut renderer = test_renderer();
+    assert!(matches!(renderer.register_effect(effect.clone(), vec![0; 2]), Err(RendererError::InvalidUniforms(2, 4))));
+    let texid = renderer.register_effect(effect, 1.0f32.to_ne_bytes().to_vec()).unwrap();
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, texid, UNCLIPPED);
+    let data = frame.draw_data();
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), RED);
+
+    renderer.set_effect_uniform(texid, "time", &[0.0]).unwrap();
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), BLUE);
+
+    // a failed update leaves the texture as it was
+    assert!(matches!(renderer.set_effect_uniform(texid, "time", &[1.0, 1.0]), Err(RendererError::InvalidUniforms(8, 4))));
+    assert!(matches!(renderer.set_effect_uniform(texid, "speed", &[1.0]), Err(RendererError::UnknownUniform(name)) if name == "speed"));
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), BLUE);
+}
+
+#[test]
+fn scaled_render_follows_the_canvas_scale() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    // 9.2 device pixels on a 2x canvas, at half resolution the edge would land past pixel 9
+    frame.add_rect(list, [0.0, 0.0, 4.6, 8.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+
+    let mut surface = raster_surface(16, 16);
+    surface.canvas().scale((2.0, 2.0));
+    renderer.render_imgui_scaled(surface.canvas(), data, 1.0, skia_safe::SamplingOptions::default()).unwrap();
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(8, 8), RED);
+    assert_eq!(pixels.at(9, 8), TRANSPARENT);
+}
+
+#[test]
+fn half_scale_render_fills_the_target() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+
+    for device_scale in &[1.0, 2.0] {
+        let size = (8.0 * device_scale) as i32;
+        let mut surface = raster_surface(size, size);
+        surface.canvas().scale((*device_scale, *device_scale));
+        renderer.render_imgui_scaled(surface.canvas(), data, 0.5, skia_safe::SamplingOptions::default()).unwrap();
+        let pixels = read_pixels(&mut surface);
+
+        assert_eq!(pixels.at(0, 0), RED);
+        assert_eq!(pixels.at(size - 1, size - 1), RED);
+    }
+}
+
+#[test]
+fn shared_clips_draw_the_same_as_separate_ones() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let left = [0.0, 0.0, 4.0, 8.0];
+    let right = [4.0, 0.0, 8.0, 8.0];
+
+    // every command stretches over the whole frame, only its clip keeps it to one half
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], RED, font, left);
+    frame.add_rect(list, [0.0, 4.0, 8.0, 8.0], GREEN, font, left);
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], BLUE, font, right);
+    frame.add_rect(list, [0.0, 6.0, 8.0, 8.0], WHITE, font, left);
+
+    let mut surface = raster_surface(8, 8);
+    let save_count = surface.canvas().save_count();
+    renderer.render_imgui(surface.canvas(), frame.draw_data());
+    assert_eq!(surface.canvas().save_count(), save_count);
+
+    // the last command's clip doesn't outlive the render call
+    let mut paint = skia_safe::Paint::default();
+    paint.set_color(skia_safe::Color::WHITE);
+    surface.canvas().draw_rect(skia_safe::Rect::new(6.0, 0.0, 8.0, 1.0), &paint);
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(2, 5), GREEN);
+    assert_eq!(pixels.at(2, 7), WHITE);
+    assert_eq!(pixels.at(6, 4), BLUE);
+    assert_eq!(pixels.at(6, 0), WHITE);
+    assert_eq!(renderer.frame_stats().clip_changes, 3);
+}
+
+#[test]
+fn per_command_clips_draw_the_same_pixels() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let left = [0.0, 0.0, 4.0, 8.0];
+    let right = [4.0, 0.0, 8.0, 8.0];
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], RED, font, left);
+    frame.add_rect(list, [0.0, 4.0, 8.0, 8.0], GREEN, font, left);
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], BLUE, font, right);
+    frame.add_rect(list, [0.0, 6.0, 8.0, 8.0], WHITE, font, left);
+    let data = frame.draw_data();
+
+    let shared = render(&renderer, data, 8, 8);
+    assert_eq!(renderer.frame_stats().clip_changes, 3);
+
+    renderer.set_clip_mode(ClipMode::PerCommand);
+    let per_command = render(&renderer, data, 8, 8);
+    assert_eq!(renderer.frame_stats().clip_changes, 4);
+
+    assert_eq!(per_command.data, shared.data);
+    assert_eq!(per_command.at(2, 5), GREEN);
+}
+
+#[test]
+fn icon_grid_draws_in_one_call() {
+    let mut renderer = test_renderer();
+    let icons = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED, GREEN, BLUE, WHITE]), 2, 2));
+
+    // four icons, one texel of the atlas each, in a single command the way imgui batches images
+    let mut vertices = Vec::new();
+    let mut indices = Vec::new();
+    for (i, (x, y)) in [(0.0, 0.0), (4.0, 0.0), (0.0, 4.0), (4.0, 4.0)].iter().enumerate() {
+        let (u, v) = (x / 8.0, y / 8.0);
+        vertices.extend_from_slice(&quad([*x, *y, x + 4.0, y + 4.0], [u, v, u + 0.5, v + 0.5], WHITE));
+        indices.extend(QUAD_INDICES.iter().map(|index| index + 4 * i as DrawIdx));
+    }
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_triangles(list, &vertices, &indices, icons, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+
+    assert_quadrants(&pixels, [RED, GREEN, BLUE, WHITE]);
+    assert_eq!(renderer.frame_stats().draw_calls, 1);
+}
+
+#[test]
+fn texture_sampling_applies_per_texture() {
+    let mut renderer = test_renderer();
+    let img = rgba_image(&[RED, BLUE]);
+    let smooth = renderer.register_image(Renderer::load_image_rgba8(&img, 2, 1));
+    let sharp = renderer.register_image(Renderer::load_image_rgba8(&img, 2, 1));
+    let trilinear = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Linear, skia_safe::MipmapMode::Linear);
+    renderer.set_texture_sampling(smooth, trilinear).unwrap();
+
+    // mipmaps are built for sampling that reads them
+    let image = |texid: TextureId| renderer.images[&texid.id()].shader().and_then(|shader| shader.image()).unwrap().0;
+    assert!(image(smooth).has_mipmaps());
+    assert!(!image(sharp).has_mipmaps());
+
+    let mut frame = TestFrame::new(8.0, 2.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 1.0], WHITE, smooth, UNCLIPPED);
+    frame.add_rect(list, [0.0, 1.0, 8.0, 2.0], WHITE, sharp, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 2);
+
+    // just left of the middle, linear mixes in the right texel and nearest doesn't
+    let blended = pixels.at(3, 0);
+    assert!(blended[0] > 0 && blended[2] > 0);
+    assert_eq!(pixels.at(3, 1), RED);
+
+    assert!(matches!(renderer.set_texture_sampling(TextureId::new(999), trilinear), Err(RendererError::UnknownTexture(_))));
+}
+
+#[test]
+fn failed_font_build_falls_back_to_the_builtin_atlas() {
+    with_context(|im_context| {
+        with_empty_atlas(im_context, |im_context| {
+            let mut renderer = Renderer::new(im_context);
+
+            assert!(matches!(renderer.take_last_error(), Some(RendererError::EmptyFontAtlas)));
+            assert_eq!(im_context.fonts().tex_id, renderer.font_texture_id());
+            let fallback = renderer.font_atlas_image();
+            assert_eq!((fallback.width(), fallback.height()), (1, 1));
+
+            // shapes still draw, the fallback samples as full coverage wherever imgui points
+            let mut frame = TestFrame::new(4.0, 4.0);
+            let list = frame.add_list();
+            frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], [0.3, 0.7, 0.3, 0.7], RED, renderer.font_texture_id(), UNCLIPPED);
+            assert_eq!(render(&renderer, frame.draw_data(), 4, 4).at(2, 2), RED);
+        });
+    });
+}
+
+#[test]
+fn render_imgui_clipped_draws_only_inside_the_rects() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, font, UNCLIPPED);
+    // in the gap between the rects but inside their bounds, clipped by skia rather than culled
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], GREEN, font, [5.0, 0.0, 7.0, 4.0]);
+    // past the bounds of both rects, culled before drawing
+    frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], BLUE, font, [13.0, 13.0, 16.0, 16.0]);
+
+    let mut surface = raster_surface(16, 16);
+    let damage = [skia_safe::Rect::new(0.0, 0.0, 4.0, 4.0), skia_safe::Rect::new(8.0, 8.0, 12.0, 12.0)];
+    renderer.render_imgui_clipped(surface.canvas(), frame.draw_data(), &damage);
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(10, 10), RED);
+    assert_eq!(pixels.at(6, 2), TRANSPARENT);
+    assert_eq!(pixels.at(2, 10), TRANSPARENT);
+    assert_eq!(pixels.at(14, 14), TRANSPARENT);
+    assert_eq!(renderer.frame_stats().draw_calls, 2);
+}
+
+#[test]
+fn difference_clips_off_the_canvas_are_not_culled() {
+    let mut renderer = test_renderer();
+    renderer.set_clip_op(skia_safe::ClipOp::Difference);
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [20.0, 20.0, 30.0, 30.0]);
+
+    // a hole off the canvas cuts nothing out, the command isn't skipped as clipped away
+    assert_eq!(render(&renderer, frame.draw_data(), 8, 8).at(4, 4), RED);
+}
+
+// display p3 with the srgb transfer function. skia-safe 0.50 can't build a color space from primaries, so this
+// takes srgb's serialized form and swaps its to-XYZ-D50 matrix, the last nine floats, for p3's
+fn display_p3() -> skia_safe::ColorSpace {
+    const P3_TO_XYZD50: [f32; 9] = [
+        0.515_102, 0.291_965, 0.157_153,
+        0.241_182, 0.692_236, 0.066_581_9,
+        -0.001_049_41, 0.041_881_8, 0.784_378,
+    ];
+
+    let mut serialized = skia_safe::ColorSpace::new_srgb().serialize().as_bytes().to_vec();
+    let matrix_start = serialized.len() - P3_TO_XYZD50.len() * 4;
+    for (bytes, value) in serialized[matrix_start..].chunks_exact_mut(4).zip(&P3_TO_XYZD50) {
+        bytes.copy_from_slice(&value.to_ne_bytes());
+    }
+
+    skia_safe::ColorSpace::deserialize(skia_safe::Data::new_copy(&serialized))
+}
+
+#[test]
+fn colors_convert_into_a_display_p3_surface() {
+    let p3 = display_p3();
+    assert!(!p3.is_srgb());
+
+    let mut renderer = test_renderer();
+    let image = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, image, UNCLIPPED);
+
+    let info = skia_safe::ImageInfo::new((8, 4), skia_safe::ColorType::RGBA8888, AlphaType::Premul, p3);
+    let mut surface = skia_safe::Surface::new_raster(&info, None, None).unwrap();
+    surface.canvas().clear(skia_safe::Color::TRANSPARENT);
+    renderer.render_imgui(surface.canvas(), frame.draw_data());
+    let mut data = vec![0u8; 8 * 4 * 4];
+    assert!(surface.read_pixels(&info, &mut data, 8 * 4, (0, 0)));
+    let pixels = Pixels { width: 8, data };
+
+    // srgb red sits inside p3's gamut at about (234, 51, 35), unconverted it would stay (255, 0, 0)
+    for pixel in &[pixels.at(2, 2), pixels.at(6, 2)] {
+        assert!((225..=240).contains(&pixel[0]) && (40..=60).contains(&pixel[1]) && (25..=45).contains(&pixel[2]), "{:?}", pixel);
+        assert_eq!(pixel[3], 255);
+    }
+}
+
+#[test]
+fn alias_shares_pixels_but_tiles_its_own_way() {
+    let mut renderer = test_renderer();
+    let repeating = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED, BLUE]), 2, 1));
+    let clamped = renderer.register_alias(repeating, skia_safe::SamplingOptions::default(), skia_safe::TileMode::Clamp).unwrap();
+    assert_ne!(clamped, repeating);
+
+    let image_id = |texid: TextureId| renderer.images[&texid.id()].shader().and_then(|shader| shader.image()).unwrap().0.unique_id();
+    assert_eq!(image_id(clamped), image_id(repeating));
+
+    // uvs running to 2 cover the texture twice over
+    let mut frame = TestFrame::new(8.0, 2.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [0.0, 0.0, 8.0, 1.0], [0.0, 0.0, 2.0, 1.0], WHITE, repeating, UNCLIPPED);
+    frame.add_rect_uv(list, [0.0, 1.0, 8.0, 2.0], [0.0, 0.0, 2.0, 1.0], WHITE, clamped, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 2);
+
+    assert_eq!(pixels.at(1, 0), RED);
+    assert_eq!(pixels.at(1, 1), RED);
+    assert_eq!(pixels.at(5, 0), RED);
+    assert_eq!(pixels.at(5, 1), BLUE);
+
+    assert!(matches!(renderer.register_alias(TextureId::new(999), skia_safe::SamplingOptions::default(), skia_safe::TileMode::Clamp), Err(RendererError::UnknownTexture(_))));
+}
+
+#[test]
+fn additive_runs_group_by_texture() {
+    let additive = |color| {
+        let mut paint = Renderer::load_image_rgba8(&rgba_image(&[color]), 1, 1);
+        paint.set_blend_mode(skia_safe::BlendMode::Plus);
+        paint
+    };
+    let mut renderer = test_renderer();
+    let red_glow = renderer.register_image(additive([64, 0, 0, 64]));
+    let blue_glow = renderer.register_image(additive([0, 0, 64, 64]));
+    let panel = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    for texid in &[red_glow, blue_glow, red_glow, panel, blue_glow] {
+        frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, *texid, UNCLIPPED);
+    }
+    let data = frame.draw_data();
+    let ungrouped = render(&renderer, data, 4, 4);
+
+    renderer.set_group_additive(true);
+    let batch = renderer.prepare(data);
+    let order: Vec<TextureId> = batch.lists[0].commands.iter().map(|command| command.texture_id).collect();
+    // the run before the panel is regrouped, nothing crosses the panel
+    assert_eq!(order, vec![red_glow, red_glow, blue_glow, panel, blue_glow]);
+
+    assert_eq!(render(&renderer, data, 4, 4).data, ungrouped.data);
+}
+
+#[test]
+fn debug_texture_colors_differ_per_texture() {
+    let mut renderer = test_renderer();
+    let first = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    let second = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    renderer.set_debug_texture_colors(true);
+
+    let mut frame = TestFrame::new(12.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, renderer.font_texture_id(), UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, first, UNCLIPPED);
+    frame.add_rect(list, [8.0, 0.0, 12.0, 4.0], WHITE, second, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 12, 4);
+
+    let colors = [pixels.at(2, 2), pixels.at(6, 2), pixels.at(10, 2)];
+    for color in &colors {
+        assert_eq!(color[3], 255);
+        assert_ne!(*color, WHITE);
+    }
+    assert_ne!(colors[0], colors[1]);
+    assert_ne!(colors[1], colors[2]);
+    assert_ne!(colors[0], colors[2]);
+}
+
+#[test]
+fn translucent_windows_leave_premultiplied_alpha_on_a_transparent_surface() {
+    let mut renderer = test_renderer();
+    let white = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    let translucent = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[[0, 0, 255, 128]]), 1, 1));
+
+    // a half transparent red window, with a straight alpha blue texture overlapping its right half
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], [255, 0, 0, 128], white, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, translucent, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    let half = 128.0 / 255.0;
+    let red = [255.0 * half, 0.0, 0.0, 255.0 * half];
+    let overlap = [red[0] * (1.0 - half), 0.0, 255.0 * half, 255.0 * (half + half * (1.0 - half))];
+    for (pixel, expected) in &[(pixels.at(2, 2), red), (pixels.at(6, 2), overlap)] {
+        for (channel, wanted) in pixel.iter().zip(expected) {
+            assert!((*channel as f32 - wanted).abs() <= 2.0, "{:?} isn't close to {:?}", pixel, expected);
+        }
+    }
+}
+
+#[test]
+fn evict_unused_releases_textures_left_out_of_recent_frames() {
+    let mut renderer = test_renderer();
+    let drawn = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+    let idle = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[BLUE]), 1, 1));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, drawn, UNCLIPPED);
+    let data = frame.draw_data();
+    for _ in 0..3 {
+        render(&renderer, data, 4, 4);
+    }
+
+    assert_eq!(renderer.evict_unused(1), vec![idle]);
+    assert!(renderer.images.contains_key(&drawn.id()));
+    assert!(!renderer.images.contains_key(&idle.id()));
+    assert!(renderer.images.contains_key(&renderer.font_texture_id().id()));
+    assert_eq!(render(&renderer, data, 4, 4).at(2, 2), RED);
+}
+
+#[test]
+fn pointer_texture_ids_resolve_through_the_resolver() {
+    // stands in for a gpu texture another backend hands imgui as a raw pointer
+    let handle = Box::new(0u64);
+    let handle_ptr = &*handle as *const u64 as usize;
+    let pointer_id = TextureId::from(handle_ptr as *const std::ffi::c_void);
+
+    let mut renderer = test_renderer();
+    let registered = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+    assert_ne!(registered, pointer_id);
+    renderer.set_texture_resolver(Some(Box::new(move |texid| {
+        if texid.id() == handle_ptr {
+            Some(Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1))
+        } else {
+            None
+        }
+    })));
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, registered, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, pointer_id, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn outline_strokes_solid_shapes_at_its_width() {
+    let mut renderer = test_renderer();
+    let white = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+    let checker = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE, BLUE, BLUE, WHITE]), 2, 2));
+
+    // a solid fill samples one texel, the image samples across its texture
+    let mut frame = TestFrame::new(32.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [4.0, 4.0, 12.0, 12.0], [0.0, 0.0, 0.0, 0.0], GREEN, white, UNCLIPPED);
+    frame.add_rect(list, [20.0, 4.0, 28.0, 12.0], WHITE, checker, UNCLIPPED);
+    let data = frame.draw_data();
+
+    for width in &[2.0, 4.0] {
+        renderer.set_outline(Some(Outline { color: skia_safe::Color::RED, width: *width }));
+        let pixels = render(&renderer, data, 32, 16);
+
+        // the stroke is centered on the edge at x = 4, so it reaches width / 2 either side
+        assert_eq!(pixels.at(4, 8), RED);
+        assert_eq!(pixels.at(8, 8), GREEN);
+        assert_eq!(pixels.at(2, 8), if *width == 4.0 { RED } else { TRANSPARENT });
+        assert_eq!(pixels.at(1, 8), TRANSPARENT);
+        assert_eq!(pixels.at(19, 8), TRANSPARENT);
+        assert_eq!(pixels.at(20, 4), WHITE);
+    }
+
+    renderer.set_outline(None);
+    assert_eq!(render(&renderer, data, 32, 16).at(4, 8), GREEN);
+}
+
+#[test]
+fn single_color_fills_take_the_uniform_color_path() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], GREEN, font, UNCLIPPED);
+    let mut gradient = quad([4.0, 0.0, 8.0, 4.0], [0.0, 0.0, 1.0, 1.0], RED);
+    gradient[2].col = BLUE;
+    frame.add_triangles(list, &gradient, &QUAD_INDICES, font, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    let stats = renderer.frame_stats();
+    assert_eq!(stats.draw_calls, 2);
+    assert_eq!(stats.uniform_color_draws, 1);
+    assert_eq!(pixels.at(2, 2), GREEN);
+}
+
+#[test]
+fn paused_uploads_defer_textures_until_resumed() {
+    let mut renderer = test_renderer();
+    renderer.set_upload_enabled(false);
+    let texid = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+    assert!(!renderer.images.contains_key(&texid.id()));
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, texid, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+
+    let pixels = render(&renderer, data, 8, 4);
+    assert_eq!(pixels.at(2, 2), TRANSPARENT);
+    assert_eq!(pixels.at(6, 2), GREEN);
+    assert!(matches!(renderer.take_last_error(), Some(RendererError::UploadDeferred(deferred)) if deferred == texid));
+
+    renderer.set_upload_enabled(true);
+    assert_eq!(render(&renderer, data, 8, 4).at(2, 2), RED);
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn dissolve_effect_reads_its_amount_from_the_uvs() {
+    // u runs across the quad, v carries the dissolve amount, texels with u below it are dissolved away
+    let effect = skia_safe::RuntimeEffect::make_for_shader(
+        "half4 main(float2 coord) { return coord.x < coord.y ? half4(0) : half4(1, 0, 0, 1); }",
+        None,
+    ).unwrap();
+
+    let mut renderer = test_renderer();
+    let texid = renderer.register_effect(effect, Vec::new()).unwrap();
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [0.0, 0.0, 8.0, 4.0], [0.0, 0.0, 1.0, 0.0], WHITE, texid, UNCLIPPED);
+    frame.add_rect_uv(list, [0.0, 4.0, 8.0, 8.0], [0.0, 0.5, 1.0, 0.5], WHITE, texid, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+
+    assert_eq!(pixels.at(1, 2), RED);
+    assert_eq!(pixels.at(6, 2), RED);
+    assert_eq!(pixels.at(1, 6), TRANSPARENT);
+    assert_eq!(pixels.at(6, 6), RED);
+}
+
+#[test]
+fn panel_scrolls_and_clips_the_ui() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 16.0, 4.0], RED, font, UNCLIPPED);
+    frame.add_rect(list, [0.0, 4.0, 16.0, 8.0], GREEN, font, UNCLIPPED);
+    frame.add_rect(list, [0.0, 8.0, 16.0, 16.0], BLUE, font, UNCLIPPED);
+
+    // an 8x8 panel at (4, 4) scrolled down by 4, so the red strip is out of view
+    let mut surface = raster_surface(16, 16);
+    let panel_rect = skia_safe::Rect::from_xywh(4.0, 4.0, 8.0, 8.0);
+    renderer.render_panel(surface.canvas(), frame.draw_data(), panel_rect, skia_safe::Vector::new(0.0, 4.0));
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(8, 5), GREEN);
+    assert_eq!(pixels.at(8, 10), BLUE);
+    assert_eq!(pixels.at(8, 2), TRANSPARENT);
+    assert_eq!(pixels.at(8, 13), TRANSPARENT);
+    assert_eq!(pixels.at(2, 5), TRANSPARENT);
+    assert_eq!(pixels.at(13, 10), TRANSPARENT);
+    assert!((4..12).all(|y| pixels.at(8, y) != RED));
+}
+
+#[test]
+fn imported_state_keeps_texture_ids() {
+    let mut old = test_renderer();
+    let red = old.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1));
+    let blue = old.register_image(Renderer::load_image_rgba8(&rgba_image(&[BLUE]), 1, 1));
+    let state = old.export_state();
+    drop(old);
+
+    let mut reloaded = test_renderer();
+    reloaded.import_state(state);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, red, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, blue, UNCLIPPED);
+    let pixels = render(&reloaded, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), BLUE);
+    // the reloaded renderer's own atlas stays, and new ids don't collide with the imported ones
+    assert!(reloaded.images.contains_key(&reloaded.font_texture_id().id()));
+    let next = reloaded.register_image(Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1));
+    assert!(next != red && next != blue);
+}
+
+#[test]
+fn supersampled_render_comes_back_at_full_resolution() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    // the left edge lands halfway across pixel 4
+    frame.add_rect(list, [4.5, 0.0, 16.0, 16.0], RED, renderer.font_texture_id(), UNCLIPPED);
+
+    let mut surface = raster_surface(16, 16);
+    renderer.render_imgui_supersampled(surface.canvas(), frame.draw_data(), 2.0).unwrap();
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(3, 8), TRANSPARENT);
+    assert_eq!(pixels.at(5, 8), RED);
+    assert_eq!(pixels.at(15, 15), RED);
+    assert_eq!(pixels.at(15, 0), RED);
+    // the 2x2 block straddling the edge averages to half coverage
+    let edge = pixels.at(4, 8);
+    assert!((edge[0] as i32 - 128).abs() <= 2 && (edge[3] as i32 - 128).abs() <= 2, "{:?}", edge);
+}
+
+#[test]
+fn font_texture_id_survives_user_textures_and_rebuilds() {
+    with_context(|im_context| {
+        // left over from another backend, the renderer has to point it at its own atlas
+        im_context.fonts().tex_id = TextureId::new(7);
+        let mut renderer = Renderer::try_new(im_context).unwrap();
+        let font_texture_id = renderer.font_texture_id();
+        assert_eq!(im_context.fonts().tex_id, font_texture_id);
+
+        let images: Vec<TextureId> = (0..3)
+            .map(|_| renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1)))
+            .collect();
+        assert!(!images.contains(&font_texture_id));
+
+        renderer.configure_fonts(im_context, |atlas| {
+            atlas.clear_fonts();
+            atlas.add_font(&default_font(20.0));
+        }).unwrap();
+        assert_eq!(renderer.font_texture_id(), font_texture_id);
+        assert_eq!(im_context.fonts().tex_id, font_texture_id);
+
+        // user textures still draw as images, the atlas as the font
+        let mut frame = TestFrame::new(8.0, 4.0);
+        let list = frame.add_list();
+        frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, images[0], UNCLIPPED);
+        frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], GREEN, font_texture_id, UNCLIPPED);
+        render(&renderer, frame.draw_data(), 8, 4);
+        let stats = renderer.frame_stats();
+        assert_eq!(stats.textures[&images[0]].draw_calls, 1);
+        assert_eq!(stats.textures[&font_texture_id].draw_calls, 1);
+        assert!(renderer.take_last_error().is_none());
+        assert_eq!(render(&renderer, frame.draw_data(), 8, 4).at(2, 2), RED);
+    });
+}
+
+#[test]
+fn font_built_after_user_images_keeps_every_id() {
+    with_context(|im_context| {
+        // the user textures are registered before this context's atlas is built, let alone rebuilt
+        let mut renderer = test_renderer();
+        let font_texture_id = renderer.font_texture_id();
+        let colors = [RED, GREEN, BLUE];
+        let images: Vec<TextureId> = colors
+            .iter()
+            .map(|color| renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[*color]), 1, 1)))
+            .collect();
+
+        renderer.reset(im_context).unwrap();
+        renderer.configure_fonts(im_context, |atlas| {
+            atlas.clear_fonts();
+            atlas.add_font(&default_font(20.0));
+        }).unwrap();
+        assert_eq!(renderer.font_texture_id(), font_texture_id);
+        assert_eq!(im_context.fonts().tex_id, font_texture_id);
+
+        // the font id resolves to the rebuilt atlas and every user id to its own image
+        let image_id = |texid: TextureId| renderer.images[&texid.id()].shader().and_then(|shader| shader.image()).unwrap().0.unique_id();
+        assert_eq!(image_id(font_texture_id), renderer.font_atlas_image().unique_id());
+
+        let mut frame = TestFrame::new(12.0, 4.0);
+        let list = frame.add_list();
+        for (i, texid) in images.iter().enumerate() {
+            let left = i as f32 * 4.0;
+            frame.add_rect(list, [left, 0.0, left + 4.0, 4.0], WHITE, *texid, UNCLIPPED);
+        }
+        let pixels = render(&renderer, frame.draw_data(), 12, 4);
+        for (i, color) in colors.iter().enumerate() {
+            assert_eq!(pixels.at(i as i32 * 4 + 2, 2), *color);
+        }
+        assert!(renderer.take_last_error().is_none());
+    });
+}
+
+#[test]
+fn multi_context_draw_data_composites_in_order() {
+    let mut renderer = test_renderer();
+    let panel_texture = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[BLUE]), 1, 1));
+
+    // two tool panels, each from its own context, overlapping in the middle
+    let mut first = TestFrame::new(12.0, 4.0);
+    let list = first.add_list();
+    first.add_rect(list, [0.0, 0.0, 8.0, 4.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    let mut second = TestFrame::new(12.0, 4.0);
+    let list = second.add_list();
+    second.add_rect(list, [4.0, 0.0, 12.0, 4.0], WHITE, panel_texture, UNCLIPPED);
+
+    let mut surface = raster_surface(12, 4);
+    renderer.render_multi_context(surface.canvas(), &[first.draw_data(), second.draw_data()]);
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), BLUE);
+    assert_eq!(pixels.at(10, 2), BLUE);
+}
+
+#[test]
+fn clip_metrics_follow_nested_windows_across_lists() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let window = [0.0, 0.0, 16.0, 16.0];
+    let child = [2.0, 2.0, 14.0, 14.0];
+    let grandchild = [4.0, 4.0, 12.0, 12.0];
+    let popup = [8.0, 8.0, 16.0, 16.0];
+
+    let mut frame = TestFrame::new(16.0, 16.0);
+    let list = frame.add_list();
+    for clip in &[window, child, grandchild, child, window] {
+        frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, font, *clip);
+    }
+    // the clip carries over between lists, so picking up where the last one left off is free
+    let list = frame.add_list();
+    for clip in &[window, popup, popup] {
+        frame.add_rect(list, [0.0, 0.0, 16.0, 16.0], RED, font, *clip);
+    }
+    render(&renderer, frame.draw_data(), 16, 16);
+    let stats = renderer.frame_stats();
+
+    assert_eq!(stats.draw_calls, 8);
+    assert_eq!(stats.clip_changes, 6);
+    assert_eq!(stats.unique_clips, 4);
+
+    // counted afresh every frame
+    render(&renderer, frame.draw_data(), 16, 16);
+    assert_eq!(renderer.frame_stats().unique_clips, 4);
+}
+
+#[test]
+fn backdrop_is_cut_to_the_command_shape() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let glass = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[TRANSPARENT]), 1, 1));
+    // shifting the backdrop right by half the frame puts the red half behind the right of the glass
+    renderer.set_backdrop_filter(glass, skia_safe::image_filters::offset((8.0, 0.0), None, None));
+
+    let mut frame = TestFrame::new(16.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, font, UNCLIPPED);
+    frame.add_rect(list, [8.0, 0.0, 16.0, 8.0], BLUE, font, UNCLIPPED);
+    // a triangle over the bottom left half of the frame, its bounds are the whole frame
+    let triangle = [
+        vertex([0.0, 0.0], [0.0, 0.0], WHITE),
+        vertex([16.0, 8.0], [0.0, 0.0], WHITE),
+        vertex([0.0, 8.0], [0.0, 0.0], WHITE),
+    ];
+    frame.add_triangles(list, &triangle, &[0, 1, 2], glass, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 16, 8);
+
+    assert_eq!(pixels.at(12, 7), RED);
+    assert_eq!(pixels.at(12, 1), BLUE);
+    assert_eq!(pixels.at(2, 1), RED);
+}
+
+#[test]
+fn explicit_ids_resolve_and_leave_registered_ids_small() {
+    let mut renderer = test_renderer();
+    let keyed = renderer.register_image_with_id(2, Renderer::load_image_rgba8(&rgba_image(&[RED]), 1, 1)).unwrap();
+    let hashed = renderer.register_image_with_id(usize::MAX, Renderer::load_image_rgba8(&rgba_image(&[BLUE]), 1, 1)).unwrap();
+    assert_eq!(keyed, TextureId::new(2));
+    assert!(matches!(
+        renderer.register_image_with_id(2, Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1)),
+        Err(RendererError::TextureIdInUse(texid)) if texid == keyed
+    ));
+
+    // counting carries on from 1, stepping over the explicit id
+    let registered: Vec<TextureId> = (0..3)
+        .map(|_| renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[GREEN]), 1, 1)))
+        .collect();
+    assert_eq!(registered, vec![TextureId::new(1), TextureId::new(3), TextureId::new(4)]);
+
+    let mut frame = TestFrame::new(12.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, keyed, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, hashed, UNCLIPPED);
+    frame.add_rect(list, [8.0, 0.0, 12.0, 4.0], WHITE, registered[1], UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 12, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), BLUE);
+    assert_eq!(pixels.at(10, 2), GREEN);
+    assert!(renderer.take_last_error().is_none());
+}
+
+#[test]
+fn skip_transparent_drops_only_no_op_commands() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+    let mut eraser_paint = Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1);
+    eraser_paint.set_blend_mode(skia_safe::BlendMode::Src);
+    let eraser = renderer.register_image(eraser_paint);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], RED, font, UNCLIPPED);
+    frame.add_rect(list, [0.0, 0.0, 8.0, 4.0], [255, 255, 255, 0], font, UNCLIPPED);
+    // source blending writes its transparency over what's below, so it isn't a no-op
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], [255, 255, 255, 0], eraser, UNCLIPPED);
+    let data = frame.draw_data();
+
+    let drawn = render(&renderer, data, 8, 4);
+    assert_eq!(renderer.frame_stats().draw_calls, 3);
+
+    renderer.set_skip_transparent(true);
+    let skipped = render(&renderer, data, 8, 4);
+    assert_eq!(renderer.frame_stats().draw_calls, 2);
+    assert_eq!(skipped.data, drawn.data);
+    assert_eq!(skipped.at(2, 2), RED);
+    assert_eq!(skipped.at(6, 2), TRANSPARENT);
+}
+
+#[test]
+fn render_to_buffer_returns_tightly_packed_rgba() {
+    let renderer = test_renderer();
+    let mut frame = TestFrame::new(5.0, 3.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [3.0, 1.0, 4.0, 2.0], [255, 0, 0, 128], renderer.font_texture_id(), UNCLIPPED);
+
+    let buffer = renderer.render_to_buffer(5, 3, frame.draw_data()).unwrap();
+    assert_eq!(buffer.len(), 5 * 3 * 4);
+    // the pixel at (3, 1) is 1 row of 5 and 3 pixels in, premultiplied
+    let offset = (5 + 3) * 4;
+    assert_eq!(&buffer[offset..offset + 4], &[128, 0, 0, 128]);
+    assert!(buffer[..offset].iter().chain(&buffer[offset + 4..]).all(|byte| *byte == 0));
+
+    assert!(matches!(renderer.render_to_buffer(0, 3, frame.draw_data()), Err(RendererError::SurfaceCreationFailed)));
+}
+
+#[test]
+fn draw_call_budget_stops_the_frame_at_the_limit() {
+    let mut renderer = test_renderer();
+    renderer.set_max_draw_calls(Some(3));
+
+    let mut frame = TestFrame::new(8.0, 2.0);
+    let list = frame.add_list();
+    for x in 0..8 {
+        frame.add_rect(list, [x as f32, 0.0, x as f32 + 1.0, 2.0], GREEN, renderer.font_texture_id(), UNCLIPPED);
+    }
+    let data = frame.draw_data();
+    let pixels = render(&renderer, data, 8, 2);
+
+    assert_eq!(renderer.frame_stats().draw_calls, 3);
+    assert!((0..3).all(|x| pixels.at(x, 1) == GREEN));
+    assert!((3..8).all(|x| pixels.at(x, 1) == TRANSPARENT));
+    assert!(matches!(renderer.take_last_error(), Some(RendererError::DrawCallBudgetExceeded(3))));
+
+    renderer.set_max_draw_calls(None);
+    assert_eq!(render(&renderer, data, 8, 2).at(7, 1), GREEN);
+    assert_eq!(renderer.frame_stats().draw_calls, 8);
+}
+
+#[test]
+fn clips_scale_with_the_framebuffer_once() {
+    let mut renderer = test_renderer();
+    let mut frame = TestFrame::new(8.0, 8.0);
+    frame.framebuffer_scale = [2.0, 2.0];
+    let list = frame.add_list();
+    // both in logical pixels, the clip lands on 4..12 device pixels with the geometry
+    frame.add_rect(list, [0.0, 0.0, 8.0, 8.0], RED, renderer.font_texture_id(), [2.0, 2.0, 6.0, 6.0]);
+    let data = frame.draw_data();
+
+    let check = |pixels: &Pixels| {
+        assert_eq!(pixels.at(3, 8), TRANSPARENT);
+        assert_eq!(pixels.at(4, 4), RED);
+        assert_eq!(pixels.at(8, 8), RED);
+        assert_eq!(pixels.at(11, 11), RED);
+        assert_eq!(pixels.at(12, 8), TRANSPARENT);
+        assert_eq!(pixels.at(8, 12), TRANSPARENT);
+    };
+
+    // scaled by the canvas matrix
+    let mut surface = raster_surface(16, 16);
+    surface.canvas().scale((2.0, 2.0));
+    renderer.render_imgui(surface.canvas(), data);
+    check(&read_pixels(&mut surface));
+
+    // scaled by the renderer, onto an unscaled canvas
+    renderer.set_round_to_device_pixels(true);
+    check(&render(&renderer, data, 16, 16));
+}
+
+#[test]
+fn texture_matrix_scrolls_the_sampled_region() {
+    let mut renderer = test_renderer();
+    let texid = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED, GREEN, BLUE, WHITE]), 4, 1));
+
+    // each texel covers 2 pixels
+    let mut frame = TestFrame::new(8.0, 2.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 2.0], WHITE, texid, UNCLIPPED);
+    let data = frame.draw_data();
+    assert_eq!(render(&renderer, data, 8, 2).at(1, 1), RED);
+
+    // a quarter of the width to the right, the last texel wraps around to the front
+    renderer.set_texture_matrix(texid, skia_safe::Matrix::translate((0.25, 0.0))).unwrap();
+    let pixels = render(&renderer, data, 8, 2);
+    assert_eq!(pixels.at(1, 1), WHITE);
+    assert_eq!(pixels.at(3, 1), RED);
+    assert_eq!(pixels.at(5, 1), GREEN);
+
+    // the next frame's offset replaces the last one's instead of adding to it
+    renderer.set_texture_matrix(texid, skia_safe::Matrix::translate((0.5, 0.0))).unwrap();
+    let pixels = render(&renderer, data, 8, 2);
+    assert_eq!(pixels.at(1, 1), BLUE);
+    assert_eq!(pixels.at(5, 1), RED);
+
+    assert!(matches!(renderer.set_texture_matrix(TextureId::new(999), skia_safe::Matrix::new_identity()), Err(RendererError::UnknownTexture(_))));
+}
+
+#[test]
+fn draw_list_hooks_run_around_each_list() {
+    use std::sync::{Arc, Mutex};
+
+    let calls: Arc<Mutex<Vec<(&str, usize)>>> = Arc::default();
+    let (before_calls, after_calls) = (calls.clone(), calls.clone());
+
+    let mut renderer = test_renderer();
+    renderer.set_draw_list_hooks(
+        Some(Box::new(move |canvas, list_index| {
+            before_calls.lock().unwrap().push(("before", list_index));
+            // 3d content between the two windows
+            if list_index == 1 {
+                canvas.draw_color(skia_safe::Color::BLUE, skia_safe::BlendMode::SrcOver);
+            }
+        })),
+        Some(Box::new(move |_, list_index| after_calls.lock().unwrap().push(("after", list_index)))),
+    );
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let back = frame.add_list();
+    frame.add_rect(back, [0.0, 0.0, 4.0, 4.0], RED, renderer.font_texture_id(), [0.0, 0.0, 4.0, 4.0]);
+    let front = frame.add_list();
+    frame.add_rect(front, [4.0, 0.0, 8.0, 4.0], GREEN, renderer.font_texture_id(), [4.0, 0.0, 8.0, 4.0]);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(*calls.lock().unwrap(), vec![("before", 0), ("after", 0), ("before", 1), ("after", 1)]);
+    // the hook isn't clipped to the back window's clip, and drew over it before the front window
+    assert_eq!(pixels.at(2, 2), BLUE);
+    assert_eq!(pixels.at(6, 2), GREEN);
+}
+
+#[test]
+fn inset_sub_images_dont_bleed_under_linear_sampling() {
+    let mut renderer = test_renderer();
+    let atlas = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[RED, RED, BLUE, BLUE, RED, RED, BLUE, BLUE]), 4, 2));
+    let linear = skia_safe::SamplingOptions::new(skia_safe::FilterMode::Linear, skia_safe::MipmapMode::None);
+    let left = renderer.register_sub_image(atlas, skia_safe::IRect::new(0, 0, 2, 2), linear, true).unwrap();
+    let right = renderer.register_sub_image(atlas, skia_safe::IRect::new(2, 0, 4, 2), linear, true).unwrap();
+    let bleeding = renderer.register_sub_image(atlas, skia_safe::IRect::new(0, 0, 2, 2), linear, false).unwrap();
+
+    let mut frame = TestFrame::new(8.0, 6.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 2.0], WHITE, left, UNCLIPPED);
+    frame.add_rect(list, [0.0, 2.0, 8.0, 4.0], WHITE, right, UNCLIPPED);
+    frame.add_rect(list, [0.0, 4.0, 8.0, 6.0], WHITE, bleeding, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 6);
+
+    assert!((0..8).all(|x| pixels.at(x, 0) == RED && pixels.at(x, 1) == RED));
+    assert!((0..8).all(|x| pixels.at(x, 2) == BLUE && pixels.at(x, 3) == BLUE));
+    // without the inset the blue sprite next door blends into the right edge
+    assert!(pixels.at(7, 5)[2] > 0);
+
+    let invalid = |renderer: &mut Renderer, rect, inset| matches!(
+        renderer.register_sub_image(atlas, rect, linear, inset),
+        Err(RendererError::InvalidRect(invalid)) if invalid == rect
+    );
+    assert!(invalid(&mut renderer, skia_safe::IRect::new(2, 0, 5, 2), false));
+    assert!(invalid(&mut renderer, skia_safe::IRect::new(-1, 0, 1, 2), false));
+    assert!(invalid(&mut renderer, skia_safe::IRect::new(1, 1, 1, 2), false));
+    assert!(invalid(&mut renderer, skia_safe::IRect::new(0, 0, 1, 2), true));
+}
+
+#[test]
+fn rotated_render_turns_geometry_and_clips_together() {
+    let renderer = test_renderer();
+    let font = renderer.font_texture_id();
+
+    // a red strip along the top, clipped to its left half, and a green strip along the bottom
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 8.0, 2.0], RED, font, [0.0, 0.0, 4.0, 8.0]);
+    frame.add_rect(list, [0.0, 6.0, 8.0, 8.0], GREEN, font, UNCLIPPED);
+
+    // a quarter turn clockwise about the center takes the top edge to the right and the left to the top
+    let mut surface = raster_surface(8, 8);
+    renderer.render_imgui_rotated(surface.canvas(), frame.draw_data(), 90.0, skia_safe::Point::new(4.0, 4.0));
+    let pixels = read_pixels(&mut surface);
+
+    assert_eq!(pixels.at(7, 1), RED);
+    assert_eq!(pixels.at(6, 3), RED);
+    assert_eq!(pixels.at(7, 6), TRANSPARENT);
+    assert_eq!(pixels.at(1, 1), GREEN);
+    assert_eq!(pixels.at(1, 6), GREEN);
+    assert_eq!(pixels.at(4, 4), TRANSPARENT);
+}
+
+#[test]
+fn identity_atlas_matrix_samples_in_pixel_units() {
+    // an external atlas laid out so its uvs are texel coordinates, covered on the left and empty on the right
+    let atlas = Renderer::captured_font_atlas(&[0xFF, 0x00], 2, 1).unwrap();
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], [0.0, 0.0, 1.0, 1.0], RED, TextureId::new(0), UNCLIPPED);
+    frame.add_rect_uv(list, [4.0, 0.0, 8.0, 4.0], [1.0, 0.0, 2.0, 1.0], RED, TextureId::new(0), UNCLIPPED);
+    let data = frame.draw_data();
+
+    let external = Renderer::with_font_atlas_matrix(atlas.clone(), skia_safe::Matrix::new_identity());
+    let pixels = render(&external, data, 8, 4);
+    assert!((0..4).all(|x| pixels.at(x, 2) == RED));
+    assert!((4..8).all(|x| pixels.at(x, 2) == TRANSPARENT));
+
+    // the usual mapping spreads uv 0 to 1 over both texels
+    let shared = Renderer::with_shared_font_atlas(atlas);
+    let pixels = render(&shared, data, 8, 4);
+    assert_eq!(pixels.at(1, 2), RED);
+    assert_eq!(pixels.at(3, 2), TRANSPARENT);
+}
+
+#[test]
+fn unchanged_lists_skip_index_revalidation() {
+    let mut renderer = test_renderer();
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(8.0, 8.0);
+    let window = frame.add_list();
+    frame.add_rect(window, [0.0, 0.0, 8.0, 4.0], RED, font, UNCLIPPED);
+    let popup = frame.add_list();
+    frame.add_rect(popup, [0.0, 4.0, 8.0, 8.0], BLUE, font, UNCLIPPED);
+
+    render(&renderer, frame.draw_data(), 8, 8);
+    assert_eq!(renderer.frame_stats().revalidated_lists, 2);
+    render(&renderer, frame.draw_data(), 8, 8);
+    assert_eq!(renderer.frame_stats().revalidated_lists, 0);
+
+    // only the list that changed is checked again
+    frame.add_rect(popup, [2.0, 4.0, 6.0, 8.0], GREEN, font, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 8);
+    assert_eq!(renderer.frame_stats().revalidated_lists, 1);
+    assert_eq!(pixels.at(4, 6), GREEN);
+
+    // a list that fails isn't remembered, it's checked and skipped every frame
+    let triangle = quad([0.0, 0.0, 8.0, 8.0], [0.0, 0.0, 1.0, 1.0], WHITE);
+    frame.add_triangles(window, &triangle, &[0, 1, 9], font, UNCLIPPED);
+    for _ in 0..2 {
+        render(&renderer, frame.draw_data(), 8, 8);
+        assert_eq!(renderer.frame_stats().revalidated_lists, 1);
+        assert_eq!(renderer.frame_stats().draw_calls, 3);
+        assert!(matches!(renderer.take_last_error(), Some(RendererError::InvalidIndices(0))));
+    }
+}
+
+#[test]
+fn white_pixel_commands_draw_with_the_solid_paint() {
+    // only the bottom right texel is covered, its center is the white pixel's uv
+    let mut renderer = Renderer::from_captured_font_atlas(&[0, 0, 0, 0xFF], 2, 2).unwrap();
+    renderer.set_white_pixel_uv(Some([0.75, 0.75]));
+    let font = renderer.font_texture_id();
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], [0.75, 0.75, 0.75, 0.75], RED, font, UNCLIPPED);
+    // text style sampling across the atlas, only its covered quadrant shows
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], RED, font, UNCLIPPED);
+    let data = frame.draw_data();
+
+    let pixels = render(&renderer, data, 8, 4);
+    assert_eq!(renderer.frame_stats().solid_draws, 1);
+    assert_eq!(pixels.at(1, 1), RED);
+    assert_eq!(pixels.at(5, 1), TRANSPARENT);
+    assert_eq!(pixels.at(7, 3), RED);
+
+    let mut solid_paint = skia_safe::Paint::default();
+    solid_paint.set_color(skia_safe::Color::WHITE);
+    solid_paint.set_color_filter(skia_safe::color_filters::blend(skia_safe::Color::BLUE, skia_safe::BlendMode::SrcIn));
+    renderer.set_solid_paint(solid_paint);
+    let pixels = render(&renderer, data, 8, 4);
+    assert_eq!(pixels.at(1, 1), BLUE);
+    assert_eq!(pixels.at(7, 3), RED);
+}
+
+#[test]
+fn solid_fills_count_as_opaque() {
+    // only the bottom right texel is covered, its center is the white pixel's uv
+    let mut renderer = Renderer::from_captured_font_atlas(&[0, 0, 0, 0xFF], 2, 2).unwrap();
+    renderer.set_white_pixel_uv(Some([0.75, 0.75]));
+    renderer.set_opaque_first(true);
+    let overlay = renderer.register_image(Renderer::load_image_rgba8(&rgba_image(&[WHITE]), 1, 1));
+
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], [0, 255, 0, 128], overlay, UNCLIPPED);
+    frame.add_rect_uv(list, [0.0, 0.0, 4.0, 4.0], [0.75, 0.75, 0.75, 0.75], RED, renderer.font_texture_id(), UNCLIPPED);
+    let data = frame.draw_data();
+
+    let batch = renderer.prepare(data);
+    let order: Vec<TextureId> = batch.lists[0].commands.iter().map(|command| command.texture_id).collect();
+    assert_eq!(order, vec![renderer.font_texture_id(), overlay]);
+
+    // the solid fill moved under the translucent overlay instead of covering it
+    let pixel = render(&renderer, data, 4, 4).at(2, 2);
+    assert_ne!(pixel, RED);
+    assert!(pixel[0] > 0 && pixel[1] > 0 && pixel[3] == 255);
+}
+
+#[test]
+fn register_images_reports_each_image_on_its_own() {
+    let mut renderer = test_renderer();
+    let red = rgba_image(&[RED]);
+    let blue = rgba_image(&[BLUE, BLUE]);
+    let short = rgba_image(&[GREEN]);
+    let images: Vec<(&[u8], i32, i32)> = vec![(&red, 1, 1), (&short, 2, 2), (&blue, 2, 1), (&red, 0, 1), (&red, -1, -1)];
+
+    let results = renderer.register_images(images.into_iter());
+    assert_eq!(results.len(), 5);
+    assert!(matches!(results[1], Err(RendererError::InvalidImageSize(2, 2))));
+    assert!(matches!(results[3], Err(RendererError::InvalidImageSize(0, 1))));
+    assert!(matches!(results[4], Err(RendererError::InvalidImageSize(-1, -1))));
+
+    // failures don't use up ids, the valid images still register in order
+    let red_id = *results[0].as_ref().unwrap();
+    let blue_id = *results[2].as_ref().unwrap();
+    assert_eq!(blue_id.id(), red_id.id() + 1);
+
+    let mut frame = TestFrame::new(8.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 4.0, 4.0], WHITE, red_id, UNCLIPPED);
+    frame.add_rect(list, [4.0, 0.0, 8.0, 4.0], WHITE, blue_id, UNCLIPPED);
+    let pixels = render(&renderer, frame.draw_data(), 8, 4);
+
+    assert_eq!(pixels.at(2, 2), RED);
+    assert_eq!(pixels.at(6, 2), BLUE);
+}
+
+#[test]
+fn device_pixel_ratio_combines_with_the_framebuffer_scale() {
+    let mut renderer = test_renderer();
+    renderer.set_device_pixel_ratio(1.5).unwrap();
+
+    // 2 * 1.5 takes the 4x4 frame to 12x12, the rect to 3..9 and the clip's right edge to 6
+    let mut frame = TestFrame::new(4.0, 4.0);
+    frame.framebuffer_scale = [2.0, 2.0];
+    let list = frame.add_list();
+    frame.add_rect(list, [1.0, 1.0, 3.0, 3.0], RED, renderer.font_texture_id(), [0.0, 0.0, 2.0, 4.0]);
+    let data = frame.draw_data();
+
+    let check = |pixels: &Pixels| {
+        assert_eq!(pixels.at(2, 4), TRANSPARENT);
+        assert_eq!(pixels.at(3, 3), RED);
+        assert_eq!(pixels.at(5, 8), RED);
+        assert_eq!(pixels.at(6, 4), TRANSPARENT);
+        assert_eq!(pixels.at(4, 9), TRANSPARENT);
+    };
+
+    // the renderer applies both scales itself when rounding
+    renderer.set_round_to_device_pixels(true);
+    check(&render(&renderer, data, 12, 12));
+
+    // otherwise framebuffer_scale is left to the canvas and only the ratio is applied here
+    renderer.set_round_to_device_pixels(false);
+    let mut surface = raster_surface(12, 12);
+    surface.canvas().scale((2.0, 2.0));
+    renderer.render_imgui(surface.canvas(), data);
+    check(&read_pixels(&mut surface));
+}
+
+#[test]
+fn device_pixel_ratio_must_be_positive_and_finite() {
+    let mut renderer = test_renderer();
+    renderer.set_device_pixel_ratio(2.0).unwrap();
+
+    for ratio in [0.0, -1.0, f32::INFINITY, f32::NAN] {
+        assert!(matches!(renderer.set_device_pixel_ratio(ratio), Err(RendererError::InvalidDevicePixelRatio(_))));
+    }
+
+    // the rejected ratios left the last good one in place
+    let mut frame = TestFrame::new(4.0, 4.0);
+    let list = frame.add_list();
+    frame.add_rect(list, [0.0, 0.0, 1.0, 1.0], RED, renderer.font_texture_id(), UNCLIPPED);
+    assert_eq!(renderer.content_bounds(frame.draw_data()), Some(skia_safe::Rect::new(0.0, 0.0, 2.0, 2.0)));
+}